@@ -0,0 +1,895 @@
+use crate::diagnostic::{Diagnostic, E_CAST, E_DEF, E_FUNC, E_MEM, E_STACK, E_TYPE, E_UNKNOWN};
+use crate::ops::{self, Operator, TypeLiteral};
+use crate::Runtime;
+use std::collections::{HashMap, HashSet};
+
+// Typen til et element på stabelen, slik den er kjent før programmet kjøres.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Type {
+    Int,
+    Float,
+    Int64,
+    Float64,
+    Bool,
+    Str,
+    Byte,
+    Char,
+    File,
+    Ptr(Option<TypeLiteral>),     // typen pekeren peker til, hvis den er kjent
+    Literal(Option<TypeLiteral>), // en bokstavelig type, f.eks. 'Helt'
+    Any,                          // alt som ikke kan vites før kjøring
+}
+
+impl Type {
+    pub fn from_literal(typ: &TypeLiteral) -> Self {
+        match typ {
+            TypeLiteral::Int => Type::Int,
+            TypeLiteral::Float => Type::Float,
+            TypeLiteral::Int64 => Type::Int64,
+            TypeLiteral::Float64 => Type::Float64,
+            TypeLiteral::Bool => Type::Bool,
+            TypeLiteral::Str => Type::Str,
+            TypeLiteral::Byte => Type::Byte,
+            TypeLiteral::Char => Type::Char,
+            TypeLiteral::Ptr => Type::Ptr(None),
+            TypeLiteral::File => Type::File,
+            TypeLiteral::Struct(id) => Type::Ptr(Some(TypeLiteral::Struct(*id))),
+        }
+    }
+
+    pub fn from_value(val: &ops::Value) -> Self {
+        match val {
+            ops::Value::Int(_) => Type::Int,
+            ops::Value::Float(_) => Type::Float,
+            ops::Value::Int64(_) => Type::Int64,
+            ops::Value::Float64(_) => Type::Float64,
+            ops::Value::Bool(_) => Type::Bool,
+            ops::Value::Str(_) => Type::Str,
+            ops::Value::Byte(_) => Type::Byte,
+            ops::Value::Char(_) => Type::Char,
+            ops::Value::Ptr(x) => Type::Ptr(Some(x.typ)),
+            ops::Value::File(_) => Type::File,
+            ops::Value::TypeLiteral(x) => Type::Literal(Some(*x)),
+            _ => Type::Any,
+        }
+    }
+
+    // om en verdi av typen `self` kan brukes der `expected` er forventet
+    pub fn fits(&self, expected: &Type) -> bool {
+        match (self, expected) {
+            (Type::Any, _) | (_, Type::Any) => true,
+            (Type::Ptr(a), Type::Ptr(b)) | (Type::Literal(a), Type::Literal(b)) => {
+                a.is_none() || b.is_none() || a == b
+            }
+            _ => self == expected,
+        }
+    }
+
+    fn join(&self, other: &Type) -> Option<Type> {
+        match (self, other) {
+            _ if self == other => Some(*self),
+            (Type::Any, _) | (_, Type::Any) => Some(Type::Any),
+            (Type::Ptr(_), Type::Ptr(_)) if self.fits(other) => Some(Type::Ptr(None)),
+            (Type::Literal(_), Type::Literal(_)) => Some(Type::Literal(None)),
+            _ => None,
+        }
+    }
+}
+
+// None betyr at koden ikke kan nås, f.eks. etter 'avslutt' eller en feil
+type Stack = Option<Vec<Type>>;
+type Sig = (&'static [Type], &'static [Type]);
+type FuncSig = (Vec<TypeLiteral>, Vec<TypeLiteral>);
+
+// Hva hver enkel operasjon tar fra og legger på stabelen. Operasjoner med flere
+// gyldige kombinasjoner har en linje for hver.
+fn signatures(op: Operator) -> &'static [Sig] {
+    use Type::*;
+    match op {
+        Operator::Add => &[
+            (&[Int, Int], &[Int]),
+            (&[Byte, Byte], &[Byte]),
+            (&[Float, Float], &[Float]),
+            (&[Int64, Int64], &[Int64]),
+            (&[Float64, Float64], &[Float64]),
+            (&[Str, Str], &[Str]),
+            (&[Str, Char], &[Str]),
+        ],
+        Operator::Sub | Operator::Mult => &[
+            (&[Int, Int], &[Int]),
+            (&[Byte, Byte], &[Byte]),
+            (&[Float, Float], &[Float]),
+            (&[Int64, Int64], &[Int64]),
+            (&[Float64, Float64], &[Float64]),
+        ],
+        Operator::AddWrap
+        | Operator::SubWrap
+        | Operator::MultWrap
+        | Operator::AddSat
+        | Operator::SubSat
+        | Operator::MultSat => &[
+            (&[Int, Int], &[Int]),
+            (&[Byte, Byte], &[Byte]),
+            (&[Int64, Int64], &[Int64]),
+        ],
+        Operator::Div => &[
+            (&[Int, Int], &[Int, Int]),
+            (&[Byte, Byte], &[Byte, Byte]),
+            (&[Float, Float], &[Float, Float]),
+            (&[Int64, Int64], &[Int64, Int64]),
+            (&[Float64, Float64], &[Float64, Float64]),
+        ],
+        Operator::Not => &[
+            (&[Bool], &[Bool]),
+            (&[Int], &[Int]),
+            (&[Byte], &[Byte]),
+            (&[Int64], &[Int64]),
+        ],
+        Operator::And | Operator::Or => &[
+            (&[Bool, Bool], &[Bool]),
+            (&[Int, Int], &[Int]),
+            (&[Byte, Byte], &[Byte]),
+            (&[Int64, Int64], &[Int64]),
+        ],
+        Operator::Xor => &[
+            (&[Bool, Bool], &[Bool]),
+            (&[Int, Int], &[Int]),
+            (&[Byte, Byte], &[Byte]),
+            (&[Int64, Int64], &[Int64]),
+        ],
+        Operator::Shl | Operator::Shr => &[
+            (&[Int, Int], &[Int]),
+            (&[Byte, Int], &[Byte]),
+            (&[Int64, Int], &[Int64]),
+        ],
+        Operator::PopCount => &[(&[Int], &[Int]), (&[Byte], &[Int]), (&[Int64], &[Int])],
+        Operator::Eq => &[
+            (&[Bool, Bool], &[Bool]),
+            (&[Int, Int], &[Bool]),
+            (&[Byte, Byte], &[Bool]),
+            (&[Float, Float], &[Bool]),
+            (&[Int64, Int64], &[Bool]),
+            (&[Float64, Float64], &[Bool]),
+            (&[Str, Str], &[Bool]),
+            (&[Char, Char], &[Bool]),
+            (&[Any, Literal(None)], &[Bool]),
+        ],
+        Operator::Lt | Operator::Le | Operator::Gt | Operator::Ge => &[
+            (&[Bool, Bool], &[Bool]),
+            (&[Int, Int], &[Bool]),
+            (&[Byte, Byte], &[Bool]),
+            (&[Float, Float], &[Bool]),
+            (&[Int64, Int64], &[Bool]),
+            (&[Float64, Float64], &[Bool]),
+            (&[Str, Str], &[Bool]),
+            (&[Char, Char], &[Bool]),
+        ],
+        Operator::Print | Operator::PrintLn => &[(&[Any], &[])],
+        Operator::Free => &[(&[Ptr(None)], &[]), (&[Str], &[])],
+        Operator::Exit => &[(&[Int], &[])],
+        Operator::InputLine => &[(&[], &[Str, Bool])],
+        Operator::InputAll => &[(&[], &[Str])],
+        Operator::InputChar => &[(&[], &[Char, Bool])],
+        Operator::Args => &[(&[], &[Int])],
+        Operator::Arg => &[(&[Int], &[Str])],
+        Operator::Env => &[(&[Str], &[Str, Bool])],
+        Operator::Len => &[(&[Str], &[Int])],
+        Operator::CharAt => &[(&[Str, Int], &[Char])],
+        Operator::Substr => &[(&[Str, Int, Int], &[Str])],
+        Operator::Find => &[(&[Str, Str], &[Int]), (&[Str, Char], &[Int])],
+        Operator::Replace => &[
+            (&[Str, Str, Str], &[Str]),
+            (&[Str, Char, Char], &[Str]),
+            (&[Str, Str, Char], &[Str]),
+            (&[Str, Char, Str], &[Str]),
+        ],
+        Operator::Split => &[
+            (&[Str, Str], &[Ptr(Some(TypeLiteral::Str)), Int]),
+            (&[Str, Char], &[Ptr(Some(TypeLiteral::Str)), Int]),
+        ],
+        Operator::Trim => &[(&[Str], &[Str])],
+        Operator::Upper | Operator::Lower => &[(&[Str], &[Str]), (&[Char], &[Char])],
+        Operator::IsDigit | Operator::IsLetter | Operator::IsSpace => &[(&[Char], &[Bool])],
+        Operator::StartsWith | Operator::EndsWith => &[(&[Str, Str], &[Bool]), (&[Str, Char], &[Bool])],
+        Operator::Open => &[(&[Str, Str], &[File])],
+        Operator::ReadLine => &[(&[File], &[Str, Bool])],
+        Operator::ReadAll => &[(&[File], &[Str])],
+        Operator::WriteFile => &[(&[Any, File], &[])],
+        Operator::Close => &[(&[File], &[])],
+        Operator::Do => &[(&[Bool], &[])],
+        _ => &[],
+    }
+}
+
+// Stabel operasjoner: hvor mange elementer de tar, og hvilke av dem som legges tilbake.
+fn shuffle(op: Operator) -> Option<(usize, &'static [usize])> {
+    match op {
+        Operator::Dup => Some((1, &[0, 0])),
+        Operator::Drop => Some((1, &[])),
+        Operator::Swap => Some((2, &[1, 0])),
+        Operator::Over => Some((2, &[0, 1, 0])),
+        Operator::Rot => Some((3, &[1, 2, 0])),
+        _ => None,
+    }
+}
+
+pub fn op_name(op: Operator) -> &'static str {
+    match op {
+        Operator::Add => "+",
+        Operator::Sub => "-",
+        Operator::Mult => "*",
+        Operator::Div => "/",
+        Operator::AddWrap => "+%",
+        Operator::SubWrap => "-%",
+        Operator::MultWrap => "*%",
+        Operator::AddSat => "+|",
+        Operator::SubSat => "-|",
+        Operator::MultSat => "*|",
+        Operator::Not => "ikke",
+        Operator::And => "og",
+        Operator::Or => "eller",
+        Operator::Xor => "xeller",
+        Operator::Shl => "<<",
+        Operator::Shr => ">>",
+        Operator::PopCount => "bitantall",
+        Operator::Eq => "=",
+        Operator::Lt => "<",
+        Operator::Le => "<=",
+        Operator::Gt => ">",
+        Operator::Ge => ">=",
+        Operator::Print => "skriv",
+        Operator::PrintLn => "skrivnl",
+        Operator::Input => "spør",
+        Operator::InputLine => "innlinje",
+        Operator::InputAll => "innalt",
+        Operator::InputChar => "innbokst",
+        Operator::Read => "@",
+        Operator::Write => "->",
+        Operator::Free => "frigjør",
+        Operator::Exit => "avslutt",
+        Operator::Cast => "omgjør",
+        Operator::Dup => "dup",
+        Operator::Drop => "slipp",
+        Operator::Swap => "snu",
+        Operator::Over => "over",
+        Operator::Rot => "rot",
+        Operator::Do => "gjør",
+        Operator::SetVar => "sett",
+        Operator::Field => "felt",
+        Operator::Const => "konst",
+        Operator::Mem => "minne",
+        Operator::Var => "var",
+        Operator::Let => "let",
+        Operator::Func => "funk",
+        Operator::Struct => "struktur",
+        Operator::If => "hvis",
+        Operator::Else => "ellers",
+        Operator::Elif => "ellvis",
+        Operator::End => "slutt",
+        Operator::In => "inni",
+        Operator::While => "når",
+        Operator::BikeShed => "--",
+        Operator::Debug => "feilsøk",
+        Operator::Args => "argumenter",
+        Operator::Arg => "argument",
+        Operator::Env => "miljø",
+        Operator::Len => "lengde",
+        Operator::CharAt => "ved",
+        Operator::Substr => "delstr",
+        Operator::Find => "finn",
+        Operator::Replace => "erstatt",
+        Operator::Split => "del",
+        Operator::Trim => "trim",
+        Operator::Upper => "store",
+        Operator::Lower => "små",
+        Operator::StartsWith => "begynnermed",
+        Operator::EndsWith => "slutterpå",
+        Operator::IsDigit => "ersiffer",
+        Operator::IsLetter => "erbokstav",
+        Operator::IsSpace => "ermellomrom",
+        Operator::Open => "åpne",
+        Operator::ReadLine => "leslinje",
+        Operator::ReadAll => "lesalt",
+        Operator::WriteFile => "skrivfil",
+        Operator::Close => "lukk",
+        Operator::Literal | Operator::Word => "",
+    }
+}
+
+enum Binding {
+    Val(Type),
+    Func(Vec<TypeLiteral>, Vec<TypeLiteral>),
+}
+
+enum Frame {
+    If {
+        ends: Vec<(Stack, ops::Pos)>,      // stabelen på slutten av hver gren
+        otherwise: Option<(Stack, ops::Pos)>, // stabelen når siste betingelse er usann
+        has_else: bool,
+    },
+    While {
+        start: Stack,
+        exit: Stack,
+    },
+    Def {
+        op: Operator,
+        name: String,
+    },
+    Func {
+        name: String,
+        returns: Vec<TypeLiteral>,
+        outer: Stack,
+    },
+    Let,
+}
+
+struct Checker<'a> {
+    prg: &'a [ops::Instruction],
+    ctx: &'a Runtime,
+    bindings: Vec<(String, Binding)>,
+    frames: Vec<(Frame, usize, usize)>, // blokk, antall bindinger før blokken, indeks til åpningen
+    funcs: HashMap<String, Option<FuncSig>>, // None når navnet har flere ulike funksjoner
+    names: HashSet<String>,
+    errors: Vec<Diagnostic>,
+}
+
+// Går gjennom programmet med en stabel av typer i stedet for verdier, og sjekker
+// hver gren av 'hvis', hver 'når' løkke og hver 'funk' før programmet kjøres.
+pub fn check(prg: &[ops::Instruction], ctx: &Runtime) -> Result<(), Vec<Diagnostic>> {
+    check_from(prg, ctx, 0, vec![])
+}
+
+// Sjekker bare instruksjonene fra `start`, med `stack` som stabelen før dem. Navn
+// og funksjoner fra hele programmet er kjent, så REPL-en kan bruke dem fra tidligere linjer.
+pub fn check_from(prg: &[ops::Instruction], ctx: &Runtime, start: usize, stack: Vec<Type>) -> Result<(), Vec<Diagnostic>> {
+    let mut checker = Checker {
+        prg,
+        ctx,
+        bindings: vec![],
+        frames: vec![],
+        funcs: HashMap::new(),
+        names: HashSet::new(),
+        errors: vec![],
+    };
+    checker.collect_names();
+    checker.run(start, stack);
+
+    if !checker.errors.is_empty() {
+        return Err(checker.errors);
+    }
+    Ok(())
+}
+
+impl Checker<'_> {
+    // Navn kan brukes før de er definert så lenge de finnes når koden kjører, så
+    // alle navn og funksjoner i programmet blir samlet før sjekken.
+    fn collect_names(&mut self) {
+        for (i, token) in self.prg.iter().enumerate() {
+            match token.op {
+                Operator::Const | Operator::Mem | Operator::Var => {
+                    if let Some(name) = self.prg.get(i + 1).and_then(|x| x.name.clone()) {
+                        self.names.insert(name);
+                    }
+                }
+                Operator::Let => {
+                    let words = self.prg[i + 1..].iter().take_while(|x| x.op == Operator::Word);
+                    self.names.extend(words.filter_map(|x| x.name.clone()));
+                }
+                Operator::Func => {
+                    if let Ok((name, params, returns, _)) = func_header(self.prg, i) {
+                        let sig = Some((params, returns));
+                        let entry = self.funcs.entry(name).or_insert(sig.clone());
+                        if *entry != sig {
+                            *entry = None
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn run(&mut self, start: usize, stack: Vec<Type>) {
+        let prg = self.prg;
+        let mut stack: Stack = Some(stack);
+        let mut i = start;
+        while i < prg.len() {
+            let token = &prg[i];
+            match token.op {
+                Operator::If => self.open(
+                    Frame::If {
+                        ends: vec![],
+                        otherwise: None,
+                        has_else: false,
+                    },
+                    i,
+                ),
+                Operator::While => self.open(
+                    Frame::While {
+                        start: stack.clone(),
+                        exit: None,
+                    },
+                    i,
+                ),
+                Operator::Do => {
+                    self.apply(&mut stack, token);
+                    match self.frames.last_mut() {
+                        Some((Frame::If { otherwise, .. }, _, _)) => *otherwise = Some((stack.clone(), token.pos.clone())),
+                        Some((Frame::While { exit, .. }, _, _)) => *exit = stack.clone(),
+                        _ => (),
+                    }
+                }
+                Operator::Else | Operator::Elif => {
+                    if let Some((Frame::If { ends, otherwise, has_else }, bindings, _)) = self.frames.last_mut() {
+                        ends.push((stack.take(), token.pos.clone()));
+                        stack = otherwise.take().and_then(|x| x.0);
+                        *has_else |= token.op == Operator::Else;
+                        self.bindings.truncate(*bindings);
+                    }
+                }
+                Operator::End => stack = self.close(stack, token),
+                Operator::Const | Operator::Mem | Operator::Var => {
+                    let name = match &prg.get(i + 1) {
+                        Some(ops::Instruction { op: Operator::Word, name: Some(name), .. }) => name.clone(),
+                        _ => {
+                            self.error(E_DEF, &token.pos, format!("Kunne ikke finne navn til '{}'", op_name(token.op)));
+                            String::new()
+                        }
+                    };
+                    self.open(Frame::Def { op: token.op, name }, i);
+                    i += 1
+                }
+                Operator::Func => match func_header(prg, i) {
+                    Ok((name, params, returns, in_i)) => {
+                        self.bindings.push((name.clone(), Binding::Func(params.clone(), returns.clone())));
+                        self.open(Frame::Func { name, returns, outer: stack.take() }, i);
+                        stack = Some(params.iter().map(Type::from_literal).collect());
+                        i = in_i
+                    }
+                    Err(e) => {
+                        self.errors.push(e);
+                        i = token.arg.unwrap()
+                    }
+                },
+                Operator::Let => {
+                    let mut j = i + 1;
+                    while prg[j].op == Operator::Word {
+                        j += 1
+                    }
+                    self.open(Frame::Let, i);
+                    let names = &prg[i + 1..j];
+                    let vals = self.take(&mut stack, token, names.len());
+                    for (name, val) in names.iter().zip(vals) {
+                        self.bindings.push((name.name.clone().unwrap(), Binding::Val(val)));
+                    }
+                    i = j
+                }
+                Operator::SetVar => {
+                    match &prg.get(i + 1) {
+                        Some(ops::Instruction { op: Operator::Word, name: Some(name), .. }) => self.set_var(&mut stack, token, name),
+                        _ => self.error(E_DEF, &token.pos, String::from("'sett' forventer et variabel navn")),
+                    }
+                    i += 1
+                }
+                Operator::Struct => i = token.arg.unwrap(),
+                _ => self.apply(&mut stack, token),
+            }
+            i += 1
+        }
+    }
+
+    fn open(&mut self, frame: Frame, i: usize) {
+        self.frames.push((frame, self.bindings.len(), i))
+    }
+
+    fn close(&mut self, mut stack: Stack, token: &ops::Instruction) -> Stack {
+        let Some((frame, bindings, open_i)) = self.frames.pop() else {
+            return stack;
+        };
+        self.bindings.truncate(bindings);
+        let opener = &self.prg[open_i];
+
+        match frame {
+            Frame::If { mut ends, otherwise, has_else } => {
+                ends.push((stack, token.pos.clone()));
+                if let (false, Some(otherwise)) = (has_else, otherwise) {
+                    ends.push(otherwise)
+                }
+                self.merge(opener, ends)
+            }
+            Frame::While { start, exit } => {
+                if let (Some(start), Some(end)) = (&start, &stack) {
+                    if !same(start, end) {
+                        self.errors.push(
+                            Diagnostic::error(E_STACK, &opener.pos, String::from("'når' løkken endrer stabelen"))
+                                .with_secondary(&token.pos, &format!("etter en runde er stabelen {}", self.show(end)))
+                                .with_note(format!("stabelen før løkken er {}, og må være lik etter hver runde", self.show(start))),
+                        );
+                    }
+                }
+                exit
+            }
+            Frame::Def { op, name } => {
+                let val = match op {
+                    Operator::Const => self.take(&mut stack, token, 1).first().copied(),
+                    Operator::Mem => match self.take(&mut stack, token, 2)[..] {
+                        [Type::Literal(typ), len] => {
+                            if !len.fits(&Type::Int) {
+                                self.error(E_TYPE, &token.pos, format!("Lengden til 'minne' må være Helt, fant {}", self.name(&len)));
+                            }
+                            Some(Type::Ptr(typ))
+                        }
+                        [Type::Any, _] => Some(Type::Ptr(None)),
+                        [typ, _] => {
+                            self.error(E_TYPE, &token.pos, format!("'minne' forventer en type, fant {}", self.name(&typ)));
+                            None
+                        }
+                        _ => None,
+                    },
+                    _ => match self.take(&mut stack, token, 2)[..] {
+                        [Type::Literal(Some(typ)), val] => {
+                            let typ = Type::from_literal(&typ);
+                            if !val.fits(&typ) {
+                                self.error(E_TYPE, &token.pos, format!("Forventet {} men fant {}", self.name(&typ), self.name(&val)));
+                            }
+                            Some(typ)
+                        }
+                        [Type::Literal(None) | Type::Any, _] => None,
+                        [typ, _] => {
+                            self.error(E_TYPE, &token.pos, format!("'var' forventer en type, fant {}", self.name(&typ)));
+                            None
+                        }
+                        _ => None,
+                    },
+                };
+                self.bindings.push((name, Binding::Val(val.unwrap_or(Type::Any))));
+                stack
+            }
+            Frame::Func { name, returns, outer } => {
+                if let Some(end) = &stack {
+                    let expected: Vec<Type> = returns.iter().map(Type::from_literal).collect();
+                    if end.len() != expected.len() || !end.iter().zip(&expected).all(|(x, y)| x.fits(y)) {
+                        self.errors.push(
+                            Diagnostic::error(
+                                E_FUNC,
+                                &token.pos,
+                                format!("Funksjonen '{}' etterlater {} men skal returnere {}", name, self.show(end), self.show(&expected)),
+                            )
+                            .with_secondary(&opener.pos, "funksjonen er definert her"),
+                        );
+                    }
+                }
+                outer
+            }
+            Frame::Let => stack,
+        }
+    }
+
+    // Alle grener som kan nås må etterlate like stabler.
+    fn merge(&mut self, opener: &ops::Instruction, ends: Vec<(Stack, ops::Pos)>) -> Stack {
+        let reachable: Vec<&(Stack, ops::Pos)> = ends.iter().filter(|x| x.0.is_some()).collect();
+        let (first, _) = reachable.first()?;
+        let mut merged = first.clone().unwrap();
+        for (stack, _) in &reachable[1..] {
+            let stack = stack.as_ref().unwrap();
+            let joined: Option<Vec<Type>> = if stack.len() == merged.len() {
+                merged.iter().zip(stack).map(|(x, y)| x.join(y)).collect()
+            } else {
+                None
+            };
+            let Some(joined) = joined else {
+                let mut err = Diagnostic::error(E_STACK, &opener.pos, String::from("grenene i 'hvis' etterlater ulike stabler"));
+                for (stack, pos) in &reachable {
+                    err = err.with_secondary(pos, &format!("stabelen er {} her", self.show(stack.as_ref().unwrap())));
+                }
+                self.errors.push(err.with_note(String::from("hver gren må legge like mange verdier av samme type på stabelen")));
+                return None;
+            };
+            merged = joined
+        }
+        Some(merged)
+    }
+
+    fn apply(&mut self, stack: &mut Stack, token: &ops::Instruction) {
+        if stack.is_none() {
+            return;
+        }
+        match token.op {
+            Operator::Literal => {
+                let typ = token.val.as_ref().map_or(Type::Any, Type::from_value);
+                stack.as_mut().unwrap().push(typ)
+            }
+            Operator::Word => self.word(stack, token),
+            Operator::Add | Operator::Sub if self.pointer_arith(stack) => (),
+            Operator::Input => {
+                let s = stack.as_mut().unwrap();
+                s.pop();
+                s.push(Type::Str)
+            }
+            Operator::Read => {
+                let typ = match self.take(stack, token, 1)[..] {
+                    [Type::Ptr(Some(TypeLiteral::Struct(_)))] => {
+                        self.error(E_MEM, &token.pos, String::from("Kan ikke lese en hel struktur, bruk et felt"));
+                        Type::Any
+                    }
+                    [Type::Ptr(Some(typ))] => Type::from_literal(&typ),
+                    [Type::Ptr(None) | Type::Any] => Type::Any,
+                    [typ] => {
+                        self.error(E_MEM, &token.pos, format!("'@' krever en peker, fant {}", self.name(&typ)));
+                        Type::Any
+                    }
+                    _ => return,
+                };
+                stack.as_mut().unwrap().push(typ)
+            }
+            Operator::Write => match self.take(stack, token, 2)[..] {
+                [_, Type::Ptr(Some(TypeLiteral::Struct(_)))] => {
+                    self.error(E_MEM, &token.pos, String::from("Kan ikke skrive en hel struktur, bruk et felt"))
+                }
+                [val, Type::Ptr(Some(typ))] if !val.fits(&Type::from_literal(&typ)) => {
+                    let msg = format!("Forventet {} men fant {}", typ, self.name(&val));
+                    self.error(E_TYPE, &token.pos, msg)
+                }
+                [_, Type::Ptr(_) | Type::Any] => (),
+                [_, typ] => {
+                    let msg = format!("'->' krever en peker, fant {}", self.name(&typ));
+                    self.error(E_TYPE, &token.pos, msg)
+                }
+                _ => (),
+            },
+            Operator::Field => {
+                let name = token.name.as_ref().unwrap();
+                let typ = match self.take(stack, token, 1)[..] {
+                    [Type::Ptr(Some(TypeLiteral::Struct(id)))] => {
+                        let Some((typ, _)) = self.ctx.structs[id].field(name) else {
+                            let msg = format!("Strukturen '{}' har ikke feltet '{}'", self.ctx.structs[id].name, name);
+                            self.error(E_UNKNOWN, &token.pos, msg);
+                            return stack.as_mut().unwrap().push(Type::Ptr(None));
+                        };
+                        Type::Ptr(Some(typ))
+                    }
+                    [Type::Ptr(None) | Type::Any] => Type::Ptr(None),
+                    [typ] => {
+                        let msg = format!("'.{}' krever en peker til en struktur, fant {}", name, self.name(&typ));
+                        self.error(E_TYPE, &token.pos, msg);
+                        Type::Ptr(None)
+                    }
+                    _ => return,
+                };
+                stack.as_mut().unwrap().push(typ)
+            }
+            Operator::Cast => {
+                let res = match self.take(stack, token, 2)[..] {
+                    [val, Type::Literal(Some(typ))] => {
+                        if !can_cast(&val, &typ) {
+                            let msg = format!("Kunne ikke omgjøre {} til {}", self.name(&val), typ);
+                            self.error(E_CAST, &token.pos, msg);
+                        }
+                        match typ {
+                            TypeLiteral::Ptr => Type::Ptr(Some(TypeLiteral::Char)),
+                            _ => Type::from_literal(&typ),
+                        }
+                    }
+                    [_, Type::Literal(None) | Type::Any] => Type::Any,
+                    [_, typ] => {
+                        let msg = format!("'omgjør' krever en bokstavelig type, fant {}", self.name(&typ));
+                        self.error(E_CAST, &token.pos, msg);
+                        Type::Any
+                    }
+                    _ => return,
+                };
+                stack.as_mut().unwrap().push(res)
+            }
+            op => {
+                if let Some((n, order)) = shuffle(op) {
+                    let taken = self.take(stack, token, n);
+                    if let Some(s) = stack {
+                        s.extend(order.iter().map(|j| taken[*j]))
+                    }
+                    return;
+                }
+                let sigs = signatures(op);
+                if sigs.is_empty() {
+                    return;
+                }
+                let n = sigs[0].0.len();
+                let args = self.take(stack, token, n);
+                if stack.is_none() {
+                    return;
+                }
+                let matching: Vec<&Sig> = sigs.iter().filter(|(ins, _)| args.iter().zip(*ins).all(|(x, y)| x.fits(y))).collect();
+                let Some((_, outs)) = matching.first() else {
+                    let accepted: Vec<String> = sigs.iter().map(|(ins, _)| self.show(ins)).collect();
+                    let msg = format!("'{}' er ikke støttet for {}", op_name(op), self.show(&args));
+                    self.errors.push(
+                        Diagnostic::error(E_TYPE, &token.pos, msg).with_note(format!("'{}' tar {}", op_name(op), accepted.join(", "))),
+                    );
+                    // fortsetter som om operasjonen hadde gått bra
+                    return stack.as_mut().unwrap().extend(sigs[0].1.iter().map(|_| Type::Any));
+                };
+                let s = stack.as_mut().unwrap();
+                if matching.iter().all(|(_, x)| x == outs) {
+                    s.extend(outs.iter())
+                } else {
+                    s.extend(outs.iter().map(|_| Type::Any))
+                }
+                if op == Operator::Exit {
+                    *stack = None
+                }
+            }
+        }
+    }
+
+    // 'Peker Helt +' og 'Helt Peker +' flytter pekeren
+    fn pointer_arith(&mut self, stack: &mut Stack) -> bool {
+        let Some(s) = stack else {
+            return false;
+        };
+        match s[..] {
+            [.., Type::Ptr(typ), Type::Int] | [.., Type::Int, Type::Ptr(typ)] => {
+                s.truncate(s.len() - 2);
+                s.push(Type::Ptr(typ));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn word(&mut self, stack: &mut Stack, token: &ops::Instruction) {
+        let name = token.name.as_ref().unwrap();
+        let binding = match self.lookup(name) {
+            Some(Binding::Val(typ)) => Binding::Val(*typ),
+            Some(Binding::Func(params, returns)) => Binding::Func(params.clone(), returns.clone()),
+            None => match self.funcs.get(name) {
+                Some(Some((params, returns))) => Binding::Func(params.clone(), returns.clone()),
+                Some(None) => Binding::Val(Type::Any),
+                None if self.names.contains(name) => Binding::Val(Type::Any),
+                None => {
+                    self.error(E_UNKNOWN, &token.pos, format!("Ukjent ord '{}'", name));
+                    *stack = None;
+                    return;
+                }
+            },
+        };
+
+        match binding {
+            Binding::Val(typ) => stack.as_mut().unwrap().push(typ),
+            Binding::Func(params, returns) => {
+                let s = stack.as_mut().unwrap();
+                let expected: Vec<Type> = params.iter().map(Type::from_literal).collect();
+                let start = s.len().saturating_sub(expected.len());
+                let args = &s[start..];
+                if args.len() != expected.len() || !args.iter().zip(&expected).all(|(x, y)| x.fits(y)) {
+                    let msg = format!("Funksjonen '{}' forventer {} men fant {}", name, self.show(&expected), self.show(args));
+                    self.error(E_FUNC, &token.pos, msg);
+                }
+                let s = stack.as_mut().unwrap();
+                s.truncate(start);
+                s.extend(returns.iter().map(Type::from_literal))
+            }
+        }
+    }
+
+    fn set_var(&mut self, stack: &mut Stack, token: &ops::Instruction, name: &String) {
+        let Some(val) = self.take(stack, token, 1).first().copied() else {
+            return;
+        };
+        match self.lookup(name) {
+            Some(Binding::Val(typ)) if !val.fits(typ) => {
+                let msg = format!("Variabelen '{}' er {} men fikk {}", name, self.name(typ), self.name(&val));
+                self.error(E_TYPE, &token.pos, msg)
+            }
+            Some(_) => (),
+            None if self.names.contains(name) => (),
+            None => self.error(E_UNKNOWN, &token.pos, format!("Ukjent variabel '{}'", name)),
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        self.bindings.iter().rev().find(|(x, _)| x == name).map(|(_, x)| x)
+    }
+
+    // Tar `n` typer fra toppen av stabelen, eller rapporterer at det er for få.
+    fn take(&mut self, stack: &mut Stack, token: &ops::Instruction, n: usize) -> Vec<Type> {
+        let Some(s) = stack else {
+            return vec![];
+        };
+        if s.len() < n {
+            let msg = match token.op {
+                Operator::End | Operator::Let => format!("'{}' krever {} verdier, men stabelen har {}", op_name(self.opener(token).op), n, s.len()),
+                op => format!("'{}' krever {} argumenter, men stabelen har {}", op_name(op), n, s.len()),
+            };
+            self.error(E_STACK, &token.pos, msg);
+            *stack = None;
+            return vec![];
+        }
+        s.split_off(s.len() - n)
+    }
+
+    fn opener<'b>(&'b self, token: &'b ops::Instruction) -> &'b ops::Instruction {
+        match (token.op, token.arg) {
+            (Operator::End, Some(i)) => &self.prg[i],
+            _ => token,
+        }
+    }
+
+    fn error(&mut self, code: u16, pos: &ops::Pos, msg: String) {
+        self.errors.push(Diagnostic::error(code, pos, msg));
+    }
+
+    fn name(&self, typ: &Type) -> String {
+        match typ {
+            Type::Int => String::from("Helt"),
+            Type::Float => String::from("Flyt"),
+            Type::Int64 => String::from("Helt64"),
+            Type::Float64 => String::from("Flyt64"),
+            Type::Bool => String::from("Bool"),
+            Type::Str => String::from("Str"),
+            Type::Byte => String::from("Byte"),
+            Type::Char => String::from("Bokst"),
+            Type::File => String::from("Fil"),
+            Type::Ptr(Some(TypeLiteral::Struct(id))) => self.ctx.structs[*id].name.clone(),
+            Type::Ptr(_) => String::from("Peker"),
+            Type::Literal(_) => String::from("TypeLitr"),
+            Type::Any => String::from("?"),
+        }
+    }
+
+    fn show(&self, stack: &[Type]) -> String {
+        let names: Vec<String> = stack.iter().map(|x| self.name(x)).collect();
+        format!("[{}]", names.join(" "))
+    }
+}
+
+fn same(a: &[Type], b: &[Type]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(x, y)| x.join(y).is_some())
+}
+
+fn can_cast(val: &Type, typ: &TypeLiteral) -> bool {
+    match (typ, val) {
+        (_, Type::Any) => true,
+        (TypeLiteral::Int | TypeLiteral::Int64, Type::Int | Type::Int64 | Type::Float | Type::Float64) => true,
+        (TypeLiteral::Int | TypeLiteral::Int64, Type::Bool | Type::Str | Type::Char | Type::Byte) => true,
+        (TypeLiteral::Char, Type::Int | Type::Byte) => true,
+        (TypeLiteral::Byte, Type::Int | Type::Char) => true,
+        (TypeLiteral::Float | TypeLiteral::Float64, Type::Int | Type::Int64 | Type::Float | Type::Float64) => true,
+        (TypeLiteral::Float | TypeLiteral::Float64, Type::Bool | Type::Str) => true,
+        (TypeLiteral::Str, Type::Int | Type::Int64 | Type::Float | Type::Float64 | Type::Bool) => true,
+        (TypeLiteral::Str, Type::Ptr(x)) => matches!(x, None | Some(TypeLiteral::Char)),
+        (TypeLiteral::Ptr, Type::Str) => true,
+        _ => false,
+    }
+}
+
+// Leser 'funk <navn> <argument typer> -- <retur typer> inni', og gir også indeksen til 'inni'.
+pub fn func_header(
+    prg: &[ops::Instruction],
+    i: usize,
+) -> Result<(String, Vec<TypeLiteral>, Vec<TypeLiteral>, usize), Diagnostic> {
+    let Some(name) = prg.get(i + 1).filter(|x| x.op == Operator::Word).and_then(|x| x.name.clone()) else {
+        return Err(Diagnostic::error(E_FUNC, &prg[i].pos, String::from("Kunne ikke finne navn til funksjon")));
+    };
+    let mut params = vec![];
+    let mut returns = vec![];
+    let mut all_params_found = false;
+    let mut j = i + 2;
+    while prg.get(j).is_some_and(|x| x.op != Operator::In) {
+        match (&prg[j].op, &prg[j].val) {
+            (Operator::BikeShed, _) => all_params_found = true,
+            (_, Some(ops::Value::TypeLiteral(typ))) if all_params_found => returns.push(*typ),
+            (_, Some(ops::Value::TypeLiteral(typ))) => params.push(*typ),
+            _ => {
+                return Err(Diagnostic::error(
+                    E_TYPE,
+                    &prg[j].pos,
+                    format!("Forventet en type i funksjonen '{}'", name),
+                ))
+            }
+        }
+        j += 1
+    }
+    if j >= prg.len() {
+        return Err(Diagnostic::error(E_FUNC, &prg[i].pos, format!("Funksjonen '{}' mangler 'inni'", name)));
+    }
+    Ok((name, params, returns, j))
+}
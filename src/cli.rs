@@ -0,0 +1,141 @@
+use crate::trace;
+
+pub const USAGE: &str = "Bruk:
+    aepp [flagg] <fil> [-- argumenter]        kjør et program
+    aepp kjør [flagg] <fil> [-- argumenter]   kjør et program
+    aepp sjekk <fil>...                      se etter feil uten å kjøre
+    aepp fmt [--sjekk] <fil>...              rykk inn blokker med 4 mellomrom
+    aepp test [mappe]                        kjør alle *_test.ae filer i mappen (standard 'test')
+    aepp disasm <fil>                        skriv ut instruksjonene programmet blir til
+    aepp [-- argumenter]                     start REPL-en
+
+Flagg for 'kjør':
+    -d                      kjør med feilsøkeren
+    --spor[=fil]            skriv ut hver instruksjon som kjøres
+    --spor-fil=fil.ae       spor bare instruksjoner fra denne filen
+    --spor-linjer=10-20     spor bare instruksjoner på disse linjene
+    --spor-funk=navn        spor bare instruksjoner i denne funksjonen
+    --profiler[=fil]        skriv ut en profil når programmet er ferdig
+
+    -h, --hjelp             vis denne teksten
+    -V, --versjon           vis versjonen";
+
+#[derive(Default)]
+pub struct RunOptions {
+    pub path: String,
+    pub args: Vec<String>, // argumentene etter '--'
+    pub debug: bool,
+    pub trace: Option<String>, // tom for stderr
+    pub filter: trace::Filter,
+    pub profile: bool,
+    pub folded: Option<String>,
+}
+
+pub enum Command {
+    Repl(Vec<String>),
+    Run(RunOptions),
+    Check(Vec<String>),
+    Fmt { paths: Vec<String>, check: bool },
+    Test(String),
+    Disasm(String),
+    Help,
+    Version,
+}
+
+// `args` er argumentene uten navnet på programmet.
+pub fn parse(args: &[String]) -> Result<Command, String> {
+    let (args, program_args) = match args.iter().position(|x| x == "--") {
+        Some(k) => (&args[..k], args[k + 1..].to_vec()),
+        None => (args, vec![]),
+    };
+    if args.iter().any(|x| matches!(x.as_str(), "-h" | "--hjelp" | "--help")) {
+        return Ok(Command::Help);
+    }
+    if args.iter().any(|x| matches!(x.as_str(), "-V" | "--versjon" | "--version")) {
+        return Ok(Command::Version);
+    }
+
+    let (cmd, rest) = match args.first().map(String::as_str) {
+        Some(x @ ("kjør" | "sjekk" | "fmt" | "test" | "disasm")) => (x, &args[1..]),
+        _ => ("", args),
+    };
+    if cmd != "kjør" && !cmd.is_empty() && !program_args.is_empty() {
+        return Err(format!("'{}' tar ikke argumenter etter '--'", cmd));
+    }
+
+    match cmd {
+        "" if rest.is_empty() => Ok(Command::Repl(program_args)),
+        "" | "kjør" => parse_run(rest, program_args).map(Command::Run),
+        "sjekk" => Ok(Command::Check(paths(cmd, rest, &[])?)),
+        "fmt" => {
+            let paths = paths(cmd, rest, &["--sjekk"])?;
+            Ok(Command::Fmt {
+                check: rest.iter().any(|x| x == "--sjekk"),
+                paths,
+            })
+        }
+        "test" => match paths(cmd, rest, &[])?.as_slice() {
+            [] => Ok(Command::Test(String::from("test"))),
+            [dir] => Ok(Command::Test(dir.clone())),
+            _ => Err(String::from("'test' tar bare én mappe")),
+        },
+        _ => match paths(cmd, rest, &[])?.as_slice() {
+            [path] => Ok(Command::Disasm(path.clone())),
+            _ => Err(format!("'{}' tar én fil", cmd)),
+        },
+    }
+}
+
+fn parse_run(args: &[String], program_args: Vec<String>) -> Result<RunOptions, String> {
+    let mut opts = RunOptions {
+        args: program_args,
+        ..Default::default()
+    };
+    let mut paths = vec![];
+    for arg in args {
+        if !arg.starts_with('-') {
+            paths.push(arg.clone());
+            continue;
+        }
+        match arg.split_once('=').unwrap_or((arg, "")) {
+            ("-d", _) => opts.debug = true,
+            ("--spor", file) => opts.trace = Some(file.to_string()),
+            ("--profiler", file) => {
+                opts.profile = true;
+                opts.folded = Some(file.to_string()).filter(|x| !x.is_empty())
+            }
+            ("--spor-fil", file) => opts.filter.file = Some(file.to_string()),
+            ("--spor-funk", func) => opts.filter.func = Some(func.to_string()),
+            ("--spor-linjer", lines) => {
+                let Some(lines) = trace::parse_lines(lines) else {
+                    return Err(format!("'--spor-linjer' forventer 'linje' eller 'fra-til', fant '{}'", lines));
+                };
+                opts.filter.lines = Some(lines)
+            }
+            _ => return Err(format!("Ukjent flagg '{}'", arg)),
+        }
+    }
+    match paths.as_slice() {
+        [path] => opts.path = path.clone(),
+        [] => return Err(String::from("'kjør' forventer en fil")),
+        [_, rest @ ..] => {
+            return Err(format!(
+                "Forventet én fil, fant også '{}'. Argumenter til programmet skrives etter '--'",
+                rest.join(" ")
+            ))
+        }
+    }
+    Ok(opts)
+}
+
+// Stiene i `args`. Alle flagg må være i `allowed`.
+fn paths(cmd: &str, args: &[String], allowed: &[&str]) -> Result<Vec<String>, String> {
+    if let Some(flag) = args.iter().find(|x| x.starts_with('-') && !allowed.contains(&x.as_str())) {
+        return Err(format!("Ukjent flagg '{}' for '{}'", flag, cmd));
+    }
+    let paths: Vec<String> = args.iter().filter(|x| !x.starts_with('-')).cloned().collect();
+    if paths.is_empty() && cmd != "test" {
+        return Err(format!("'{}' forventer minst én fil", cmd));
+    }
+    Ok(paths)
+}
@@ -1,12 +1,16 @@
-use crate::ops;
+use crate::diagnostic::{Diagnostic, E_BLOCK, E_DEF, E_UNKNOWN};
+use crate::{ops, report_err};
 
+// Finner 'slutt' til hver blokk. Feil stopper ikke letingen, så alle blokker
+// som ikke passer sammen blir skrevet ut samtidig.
 pub fn cross_reference(
-    prg: &mut Vec<ops::Instruction>,
-    _ctx: &crate::Runtime,
-) -> Result<Vec<ops::Instruction>, (&'static str, ops::Pos)> {
+    prg: &mut [ops::Instruction],
+    ctx: &mut crate::Runtime,
+) -> Result<Vec<ops::Instruction>, Vec<Diagnostic>> {
+    let mut errors: Vec<Diagnostic> = vec![];
     let mut stack: Vec<usize> = vec![];
     for i in 0..prg.len() {
-        let token = prg[i].op.clone();
+        let token = prg[i].op;
         match token {
             ops::Operator::If => stack.push(i),
             ops::Operator::While => stack.push(i),
@@ -14,93 +18,255 @@ pub fn cross_reference(
             ops::Operator::Mem => stack.push(i),
             ops::Operator::Func => stack.push(i),
             ops::Operator::Let => stack.push(i),
+            ops::Operator::Var => stack.push(i),
+            ops::Operator::Struct => {
+                stack.push(i);
+                match struct_def(prg, i, ctx) {
+                    Ok(def) => ctx.structs.push(def),
+                    Err(e) => errors.push(e),
+                }
+            }
+            ops::Operator::Word => {
+                let name = prg[i].name.as_ref().unwrap();
+                if let Some(id) = ctx.structs.iter().position(|x| &x.name == name) {
+                    prg[i].op = ops::Operator::Literal;
+                    prg[i].val = Some(ops::Value::TypeLiteral(ops::TypeLiteral::Struct(id)));
+                    prg[i].name = None;
+                }
+            }
+            ops::Operator::Field => {
+                let name = prg[i].name.as_ref().unwrap();
+                if !ctx.structs.iter().any(|x| x.field(name).is_some()) {
+                    errors.push(Diagnostic::error(E_UNKNOWN, &prg[i].pos, format!("Ukjent felt '.{}'", name)));
+                }
+            }
             ops::Operator::Else | ops::Operator::Elif => {
-                let if_i = stack.pop().unwrap();
-                if prg[if_i].op == ops::Operator::Do {
-                    if prg[prg[if_i].arg.unwrap()].op == ops::Operator::If {
+                let Some(if_i) = stack.pop() else {
+                    errors.push(Diagnostic::error(
+                        E_BLOCK,
+                        &prg[i].pos,
+                        format!("'{}' uten 'hvis-gjør' foran", token_name(&prg[i])),
+                    ));
+                    continue;
+                };
+                let opener = if prg[if_i].op == ops::Operator::Do { prg[if_i].arg } else { None };
+                match opener.map(|x| prg[x].op) {
+                    Some(ops::Operator::If) => {
                         prg[if_i].arg = Some(i);
                         stack.push(i)
-                    } else if prg[prg[if_i].arg.unwrap()].op == ops::Operator::Elif {
-                        stack.push(prg[if_i].arg.unwrap());
+                    }
+                    Some(ops::Operator::Elif) => {
+                        stack.push(opener.unwrap());
                         prg[if_i].arg = Some(i);
                         stack.push(i)
                     }
-                } else {
-                    return Err((
-                        "'ellers' kan bare stenge 'hvis-gjør' og 'ellvis-gjør' blokker",
-                        prg[if_i].pos.clone(),
-                    ));
+                    // 'gjør' uten noe foran er allerede skrevet ut som en feil
+                    None if prg[if_i].op == ops::Operator::Do => stack.push(i),
+                    _ => {
+                        errors.push(
+                            Diagnostic::error(
+                                E_BLOCK,
+                                &prg[i].pos,
+                                format!(
+                                    "'{}' kan bare stenge 'hvis-gjør' og 'ellvis-gjør' blokker",
+                                    token_name(&prg[i])
+                                ),
+                            )
+                            .with_secondary(&prg[opener.unwrap_or(if_i)].pos, "blokken som blir stengt"),
+                        );
+                        stack.push(if_i)
+                    }
                 }
             }
             // ops::Operator::Elif => stack.push(i),
             ops::Operator::End => {
-                let block_i = stack.pop().unwrap();
+                let Some(block_i) = stack.pop() else {
+                    errors.push(
+                        Diagnostic::error(E_BLOCK, &prg[i].pos, String::from("'slutt' uten en blokk å stenge"))
+                            .with_note(String::from("sjekk om blokken over har en 'slutt' for mye")),
+                    );
+                    continue;
+                };
 
                 if prg[block_i].op == ops::Operator::Else {
                     prg[block_i].arg = Some(i);
-                    let mut j: isize = stack.len() as isize - 1;
-                    'else_loop: while j != -1 {
-                        let pot_else = stack.pop().unwrap();
-                        if prg[pot_else].op == ops::Operator::Elif {
-                            prg[pot_else].arg = Some(i);
-                            j -= 1
-                        } else {
-                            stack.push(pot_else);
-                            break 'else_loop;
-                        }
-                    }
+                    close_elifs(prg, &mut stack, i);
                 } else if prg[block_i].op == ops::Operator::Do {
-                    let if_elif_i = prg[block_i].arg.clone().unwrap();
-                    if prg[prg[block_i].arg.unwrap()].op == ops::Operator::While {
+                    let Some(if_elif_i) = prg[block_i].arg else {
+                        continue;
+                    };
+                    if prg[if_elif_i].op == ops::Operator::While {
                         prg[i].arg = prg[block_i].arg;
                         prg[block_i].arg = Some(i);
-                    } else if prg[prg[block_i].arg.unwrap()].op == ops::Operator::If  {
+                    } else if prg[if_elif_i].op == ops::Operator::If {
                         prg[block_i].arg = Some(i);
-                    } else if prg[prg[block_i].arg.unwrap()].op == ops::Operator::Elif {
+                    } else if prg[if_elif_i].op == ops::Operator::Elif {
                         prg[if_elif_i].arg = Some(i);
                         prg[block_i].arg = Some(i);
-                        let mut j: isize = stack.len() as isize - 1;
-                        'else_loop: while j != -1 {
-                            let pot_else = stack.pop().unwrap();
-                            if prg[pot_else].op == ops::Operator::Elif {
-                                prg[pot_else].arg = Some(i);
-                                j -= 1
-                            } else {
-                                stack.push(pot_else);
-                                break 'else_loop;
-                            }
-                        }
+                        close_elifs(prg, &mut stack, i);
                     }
-                } else if prg[block_i].op == ops::Operator::Const {
-                    prg[i].arg = Some(block_i);
-                } else if prg[block_i].op == ops::Operator::Mem {
+                } else if prg[block_i].op == ops::Operator::Const
+                    || prg[block_i].op == ops::Operator::Mem
+                    || prg[block_i].op == ops::Operator::Var
+                {
                     prg[i].arg = Some(block_i);
                 } else if prg[block_i].op == ops::Operator::Func {
                     prg[i].arg = Some(block_i);
                     prg[block_i].arg = Some(i);
                 } else if prg[block_i].op == ops::Operator::Let {
                     prg[i].arg = Some(block_i);
+                } else if prg[block_i].op == ops::Operator::Struct {
+                    prg[block_i].arg = Some(i);
+                } else if prg[block_i].op == ops::Operator::If {
+                    ctx.warnings.push(
+                        Diagnostic::warning(E_BLOCK, &prg[block_i].pos, String::from("'hvis' uten 'gjør' kjører alltid blokken"))
+                            .with_note(String::from("skriv 'hvis <betingelse> gjør ... slutt'")),
+                    );
+                } else if prg[block_i].op == ops::Operator::Elif || prg[block_i].op == ops::Operator::While {
+                    errors.push(
+                        Diagnostic::error(
+                            E_BLOCK,
+                            &prg[i].pos,
+                            format!("'slutt' før '{}' har fått en 'gjør'", token_name(&prg[block_i])),
+                        )
+                        .with_secondary(&prg[block_i].pos, "blokken som blir stengt"),
+                    );
                 }
             }
             ops::Operator::Do => {
-                let block_i = stack.pop().unwrap();
-                prg[i].arg = Some(block_i);
+                // 'gjør' legges på stabelen selv uten noe foran, så 'slutt' etter den fortsatt passer
+                let block_i = stack.pop();
+                match block_i.map(|x| prg[x].op) {
+                    Some(ops::Operator::If | ops::Operator::Elif | ops::Operator::While) => prg[i].arg = block_i,
+                    _ => {
+                        let mut err = Diagnostic::error(
+                            E_BLOCK,
+                            &prg[i].pos,
+                            String::from("'gjør' uten 'hvis', 'ellvis' eller 'når' foran"),
+                        );
+                        if let Some(block_i) = block_i {
+                            err = err.with_secondary(&prg[block_i].pos, "den åpne blokken");
+                            stack.push(block_i);
+                        }
+                        errors.push(err);
+                    }
+                }
                 stack.push(i)
             }
-            ops::Operator::In => {
-                let param_i = stack.pop().unwrap();
-                prg[i].arg = Some(param_i);
-                stack.push(param_i)
-            }
+            ops::Operator::In => match stack.last() {
+                Some(&param_i) if matches!(prg[param_i].op, ops::Operator::Func | ops::Operator::Let) => {
+                    prg[i].arg = Some(param_i);
+                }
+                last => {
+                    let mut err =
+                        Diagnostic::error(E_BLOCK, &prg[i].pos, String::from("'inni' uten 'funk' eller 'let' foran"));
+                    if let Some(&block_i) = last {
+                        err = err.with_secondary(&prg[block_i].pos, "den åpne blokken");
+                    }
+                    errors.push(err);
+                }
+            },
             _ => (),
         }
     }
     // for (i, inst) in prg.iter().enumerate() {
     //     println!("{}: {}", i, inst)
     // }
-    if stack.len() > 0 {
-        return Err(("ikke stengt blokk", prg[stack.pop().unwrap()].pos.clone()));
+    let end = prg.last().map(|x| x.pos.clone());
+    for (k, &(mut block_i)) in stack.iter().enumerate() {
+        // en 'ellvis' kjede blir skrevet ut én gang, gjennom den siste blokken
+        let chained = |x: &usize| {
+            prg[*x].op == ops::Operator::Elif
+                || prg[*x].op == ops::Operator::Do && prg[*x].arg.is_some_and(|y| prg[y].op == ops::Operator::Elif)
+        };
+        if prg[block_i].op == ops::Operator::Elif && stack.get(k + 1).is_some_and(chained) {
+            continue;
+        }
+        // 'gjør' uten noe foran har allerede fått en feil
+        if prg[block_i].op == ops::Operator::Do && prg[block_i].arg.is_none() {
+            continue;
+        }
+        if let (ops::Operator::Do, Some(start)) = (prg[block_i].op, prg[block_i].arg) {
+            block_i = start
+        }
+        let mut err = Diagnostic::error(E_BLOCK, &prg[block_i].pos, String::from("ikke stengt blokk"));
+        if let Some(end) = &end {
+            err = err.with_secondary(end, "filen slutter før blokken er stengt")
+        }
+        errors.push(err.with_note(String::from("hver blokk må stenges med 'slutt'")));
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(prg.to_vec())
+}
+
+// Lar hver 'ellvis' i kjeden som slutter ved `end` hoppe dit.
+fn close_elifs(prg: &mut [ops::Instruction], stack: &mut Vec<usize>, end: usize) {
+    while let Some(&pot_else) = stack.last() {
+        if prg[pot_else].op != ops::Operator::Elif {
+            break;
+        }
+        prg[pot_else].arg = Some(end);
+        stack.pop();
+    }
+}
+
+fn struct_def(
+    prg: &[ops::Instruction],
+    i: usize,
+    ctx: &crate::Runtime,
+) -> Result<ops::StructDef, Diagnostic> {
+    let Some(name) = prg.get(i + 1).filter(|x| x.op == ops::Operator::Word).and_then(|x| x.name.clone()) else {
+        report_err!(E_DEF, prg[i].pos, "Kunne ikke finne navn til struktur");
+    };
+    if ctx.structs.iter().any(|x| x.name == name) {
+        report_err!(E_DEF, prg[i + 1].pos, "Strukturen '{}' er allerede definert", name);
     }
 
-    Ok(prg.clone())
+    let mut fields: Vec<(String, ops::TypeLiteral, usize)> = vec![];
+    let mut size = 0;
+    let mut j = i + 2;
+    while j < prg.len() && prg[j].op != ops::Operator::End {
+        let field = &prg[j];
+        let (ops::Operator::Word, Some(field_name)) = (field.op, &field.name) else {
+            report_err!(E_DEF, field.pos, "Forventet et feltnavn i strukturen '{}'", name);
+        };
+        if fields.iter().any(|(x, _, _)| x == field_name) {
+            report_err!(E_DEF, field.pos, "Feltet '{}' er allerede definert i '{}'", field_name, name);
+        }
+
+        let typ = match prg.get(j + 1) {
+            Some(ops::Instruction { val: Some(ops::Value::TypeLiteral(typ)), .. }) => *typ,
+            Some(ops::Instruction { op: ops::Operator::Word, name: Some(typ_name), pos, .. }) => {
+                let Some(id) = ctx.structs.iter().position(|x| &x.name == typ_name) else {
+                    report_err!(E_UNKNOWN, pos, "Ukjent type '{}'", typ_name);
+                };
+                ops::TypeLiteral::Struct(id)
+            }
+            _ => {
+                report_err!(E_DEF, field.pos, "Forventet en type til feltet '{}'", field_name);
+            }
+        };
+
+        fields.push((field_name.clone(), typ, size));
+        size += ctx.size_of(&typ);
+        j += 2;
+    }
+
+    if fields.is_empty() {
+        report_err!(E_DEF, prg[i].pos, "Strukturen '{}' må ha minst ett felt", name);
+    }
+
+    Ok(ops::StructDef { name, fields, size })
+}
+
+fn token_name(token: &ops::Instruction) -> &'static str {
+    match token.op {
+        ops::Operator::Else => "ellers",
+        ops::Operator::Elif => "ellvis",
+        ops::Operator::While => "når",
+        _ => "",
+    }
 }
@@ -0,0 +1,226 @@
+use crate::execute::Hook;
+use crate::{diagnostic, ops, Runtime};
+use std::io::{stdin, stdout, Write};
+use std::path::Path;
+
+const HELP: &str = "s, steg              kjør til neste linje
+o, over              kjør til neste linje, uten å gå inn i funksjoner
+f, fortsett          kjør til neste stoppunkt
+b, stopp <fil:linje> sett et stoppunkt, uten argument vises alle stoppunktene
+fjern <fil:linje>    fjern et stoppunkt
+stabel               skriv ut stabelen
+def [navn]           skriv ut definisjoner og variabler
+minne <start> [len]  skriv ut minnet fra start
+spor                 skriv ut funksjons kallene som er i gang
+hjelp                vis denne teksten
+avslutt              avslutt programmet";
+
+enum Mode {
+    Step,
+    Over(usize), // stopper ikke før kall dybden er tilbake på dette nivået
+    Continue,
+}
+
+// Feilsøkeren stopper før første instruksjon, ved stoppunkter og ved 'feilsøk'.
+pub struct Debugger {
+    breakpoints: Vec<(String, usize)>,
+    mode: Mode,
+    last_stop: Option<(String, usize, usize)>, // fil, linje og kall dybde der feilsøkeren sist stoppet
+    quit: bool,
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Debugger {
+            breakpoints: vec![],
+            mode: Mode::Step,
+            last_stop: None,
+            quit: false,
+        }
+    }
+
+    fn should_stop(&self, ctx: &Runtime, token: &ops::Instruction) -> bool {
+        if self.on_last_stop(ctx, token) {
+            return false;
+        }
+        if token.op == ops::Operator::Debug {
+            return true;
+        }
+        let at_breakpoint = self
+            .breakpoints
+            .iter()
+            .any(|(file, l)| *l == token.pos.0 && same_file(&token.pos.2, file));
+        match self.mode {
+            Mode::Step => true,
+            Mode::Over(depth) => at_breakpoint || ctx.return_stack.len() <= depth,
+            Mode::Continue => at_breakpoint,
+        }
+    }
+
+    fn on_last_stop(&self, ctx: &Runtime, token: &ops::Instruction) -> bool {
+        self.last_stop.as_ref().is_some_and(|(file, line, depth)| {
+            *line == token.pos.0 && *file == token.pos.2 && *depth == ctx.return_stack.len()
+        })
+    }
+
+    fn prompt(&mut self, ctx: &Runtime, prg: &[ops::Instruction], i: usize) {
+        let pos = &prg[i].pos;
+        println!("--> {}:{}:{}", pos.2, pos.0, pos.1);
+        if let Some(line) = diagnostic::source_line(pos) {
+            println!("{:>4} | {}", pos.0, line);
+        }
+
+        loop {
+            print!("(feilsøk) ");
+            let _ = stdout().flush();
+            let mut input = String::new();
+            if stdin().read_line(&mut input).unwrap_or(0) == 0 {
+                self.mode = Mode::Continue;
+                return;
+            }
+            let mut args = input.split_whitespace();
+            match (args.next().unwrap_or("s"), args.next(), args.next()) {
+                ("s" | "steg", _, _) => {
+                    self.mode = Mode::Step;
+                    return;
+                }
+                ("o" | "over", _, _) => {
+                    self.mode = Mode::Over(ctx.return_stack.len());
+                    return;
+                }
+                ("f" | "fortsett", _, _) => {
+                    self.mode = Mode::Continue;
+                    return;
+                }
+                ("b" | "stopp", None, _) => {
+                    for (file, line) in &self.breakpoints {
+                        println!("{}:{}", file, line)
+                    }
+                }
+                ("b" | "stopp", Some(x), _) => match parse_location(x, pos) {
+                    Some(loc) => {
+                        println!("Stoppunkt ved {}:{}", loc.0, loc.1);
+                        self.breakpoints.push(loc)
+                    }
+                    None => println!("Forventet 'fil:linje' eller 'linje', fant '{}'", x),
+                },
+                ("fjern", Some(x), _) => {
+                    let loc = parse_location(x, pos);
+                    self.breakpoints.retain(|b| Some(b) != loc.as_ref())
+                }
+                ("stabel", _, _) => print_stack(ctx),
+                ("def", name, _) => print_defs(ctx, name),
+                ("minne", Some(start), len) => {
+                    let (Ok(start), Ok(len)) = (start.parse::<usize>(), len.unwrap_or("1").parse::<usize>()) else {
+                        println!("Forventet 'minne <start> [lengde]'");
+                        continue;
+                    };
+                    print_mem(ctx, start, len)
+                }
+                ("spor", _, _) => {
+                    println!("i {}:{}:{}", pos.2, pos.0, pos.1);
+                    for (name, pos) in ctx.call_trace(prg) {
+                        println!("  i '{}' kalt fra {}:{}:{}", name, pos.2, pos.0, pos.1)
+                    }
+                }
+                ("hjelp", _, _) => println!("{}", HELP),
+                ("avslutt", _, _) => {
+                    self.quit = true;
+                    return;
+                }
+                (x, _, _) => println!("Ukjent kommando '{}', skriv 'hjelp' for hjelp", x),
+            }
+        }
+    }
+}
+
+impl Hook for Debugger {
+    fn stop(&self) -> Option<u8> {
+        self.quit.then_some(0)
+    }
+
+    fn before(&mut self, ctx: &Runtime, prg: &[ops::Instruction], i: usize) {
+        let token = &prg[i];
+        if self.should_stop(ctx, token) {
+            self.last_stop = Some((token.pos.2.clone(), token.pos.0, ctx.return_stack.len()));
+            self.prompt(ctx, prg, i);
+        } else if !self.on_last_stop(ctx, token)
+            && self.last_stop.as_ref().is_some_and(|(_, _, depth)| ctx.return_stack.len() <= *depth)
+        {
+            // linjen er forlatt, så neste gang den kjøres kan feilsøkeren stoppe der igjen
+            self.last_stop = None
+        }
+    }
+}
+
+fn same_file(path: &str, file: &str) -> bool {
+    path == file || Path::new(path).ends_with(file)
+}
+
+// 'fil.ae:12', eller bare '12' for filen feilsøkeren står i
+fn parse_location(x: &str, pos: &ops::Pos) -> Option<(String, usize)> {
+    match x.rsplit_once(':') {
+        Some((file, line)) => Some((file.to_string(), line.parse().ok()?)),
+        None => Some((pos.2.clone(), x.parse().ok()?)),
+    }
+}
+
+fn show(ctx: &Runtime, val: &ops::Value) -> String {
+    match val {
+        ops::Value::FuncPtr(x) => {
+            let params: Vec<String> = x.params.iter().map(|x| x.to_string()).collect();
+            let returns: Vec<String> = x.returns.iter().map(|x| x.to_string()).collect();
+            format!("funk {} -- {}", params.join(" "), returns.join(" "))
+        }
+        ops::Value::Str(_) => format!("{:?} (Str)", val.to_string(ctx)),
+        _ => format!("{} ({})", val.to_string(ctx), val),
+    }
+}
+
+fn print_stack(ctx: &Runtime) {
+    if ctx.stack.is_empty() {
+        println!("Stabelen er tom");
+    }
+    for (j, val) in ctx.stack.iter().enumerate().rev() {
+        println!("{:>4}: {}", j, show(ctx, val))
+    }
+}
+
+fn print_defs(ctx: &Runtime, name: Option<&str>) {
+    let vars = ctx.vars.last().into_iter().chain(ctx.vars.first()).flat_map(|x| x.iter());
+    let mut found = false;
+    for (key, (val, _, _)) in vars {
+        if name.is_none_or(|x| x == key) {
+            println!("var {} = {}", key, show(ctx, val));
+            found = true
+        }
+    }
+    let mut defs: Vec<_> = ctx.def.iter().filter(|(key, _)| name.is_none_or(|x| x == *key)).collect();
+    defs.sort_by_key(|(key, _)| *key);
+    for (key, (val, _)) in defs {
+        match val {
+            Some(val) => println!("{} = {}", key, show(ctx, val)),
+            None => println!("{} (blir definert)", key),
+        }
+        found = true
+    }
+    if let (false, Some(name)) = (found, name) {
+        println!("'{}' er ikke definert", name)
+    }
+}
+
+fn print_mem(ctx: &Runtime, start: usize, len: usize) {
+    for ptr in start..(start + len).min(ctx.mem.len()) {
+        let state = if ctx.alloc_id(ptr).is_some() { "" } else { " (frigjort)" };
+        println!("{:>6}: {}{}", ptr, show(ctx, &ctx.mem[ptr]), state)
+    }
+    if start + len > ctx.mem.len() {
+        println!("Minnet slutter ved {}", ctx.mem.len())
+    }
+}
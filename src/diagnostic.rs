@@ -0,0 +1,161 @@
+use crate::ops::Pos;
+use std::fmt;
+use std::fs;
+
+// Feilkoder. Første siffer etter 'E' sier hvor feilen kommer fra:
+// 0 lesing av filer, 1 parsing, 2 blokker og definisjoner, 3 kjøring.
+pub const E_FILE: u16 = 1;
+pub const E_IMPORT: u16 = 2;
+pub const E_LITERAL: u16 = 101;
+pub const E_BLOCK: u16 = 201;
+pub const E_DEF: u16 = 202;
+pub const E_UNKNOWN: u16 = 203;
+pub const E_STACK: u16 = 301;
+pub const E_TYPE: u16 = 302;
+pub const E_MEM: u16 = 303;
+pub const E_FUNC: u16 = 304;
+pub const E_CAST: u16 = 305;
+pub const E_RANGE: u16 = 306;
+pub const E_IO: u16 = 307;
+pub const E_ARITH: u16 = 308;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "feil"),
+            Severity::Warning => write!(f, "advarsel"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub code: u16,
+    pub severity: Severity,
+    pub message: String,
+    pub pos: Pos,
+    pub secondary: Vec<(Pos, String)>,
+    pub notes: Vec<Note>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Note {
+    Text(String),
+    Call(String, Pos), // en funksjon som var i gang da feilen skjedde, og hvor den ble kalt fra
+}
+
+impl Diagnostic {
+    pub fn error(code: u16, pos: &Pos, message: String) -> Self {
+        Diagnostic {
+            code,
+            severity: Severity::Error,
+            message,
+            pos: pos.clone(),
+            secondary: vec![],
+            notes: vec![],
+        }
+    }
+
+    pub fn warning(code: u16, pos: &Pos, message: String) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(code, pos, message)
+        }
+    }
+
+    pub fn with_secondary(mut self, pos: &Pos, label: &str) -> Self {
+        self.secondary.push((pos.clone(), label.to_string()));
+        self
+    }
+
+    pub fn with_note(mut self, note: String) -> Self {
+        self.notes.push(Note::Text(note));
+        self
+    }
+
+    // `calls` kommer fra `Runtime::call_trace`, innerst først
+    pub fn with_calls(mut self, calls: Vec<(String, Pos)>) -> Self {
+        self.notes.extend(calls.into_iter().map(|(name, pos)| Note::Call(name, pos)));
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = std::iter::once(&self.pos)
+            .chain(self.secondary.iter().map(|(pos, _)| pos))
+            .map(|pos| pos.0.to_string().len())
+            .max()
+            .unwrap_or(1);
+
+        writeln!(f, "{}[E{:04}]: {}", self.severity, self.code, self.message)?;
+        writeln!(f, "{:w$}--> {}:{}:{}", "", self.pos.2, self.pos.0, self.pos.1, w = width)?;
+        write_snippet(f, &self.pos, '^', "", width)?;
+        for (pos, label) in &self.secondary {
+            writeln!(f, "{:w$}::: {}:{}:{}", "", pos.2, pos.0, pos.1, w = width)?;
+            write_snippet(f, pos, '-', label, width)?;
+        }
+        write_notes(f, &self.notes, width)
+    }
+}
+
+// Like kall etter hverandre, som ved rekursjon, blir slått sammen.
+fn write_notes(f: &mut fmt::Formatter, notes: &[Note], width: usize) -> fmt::Result {
+    let mut i = 0;
+    while i < notes.len() {
+        match &notes[i] {
+            Note::Text(note) => writeln!(f, "{:w$} = merk: {}", "", note, w = width)?,
+            Note::Call(name, pos) => {
+                writeln!(f, "{:w$} = i '{}' kalt fra {}:{}:{}", "", name, pos.2, pos.0, pos.1, w = width)?;
+                let repeats = notes[i..].iter().take_while(|x| *x == &notes[i]).count();
+                if repeats > 1 {
+                    writeln!(f, "{:w$}   ... {} ganger til", "", repeats - 1, w = width)?;
+                    i += repeats - 1;
+                }
+            }
+        }
+        i += 1;
+    }
+    Ok(())
+}
+
+// Skriver ut linjen `pos` peker på, med en strek under ordet.
+fn write_snippet(f: &mut fmt::Formatter, pos: &Pos, mark: char, label: &str, width: usize) -> fmt::Result {
+    let Some(line) = source_line(pos) else {
+        return Ok(());
+    };
+    // kolonnene fra lex::lex teller bytes
+    let start = (pos.1 - 1).min(line.len());
+    let start = (0..=start).rev().find(|x| line.is_char_boundary(*x)).unwrap_or(0);
+    let indent = line[..start].chars().count();
+
+    writeln!(f, "{:w$} |", "", w = width)?;
+    writeln!(f, "{:>w$} | {}", pos.0, line, w = width)?;
+    let underline = format!(
+        "{}{} {}",
+        " ".repeat(indent),
+        mark.to_string().repeat(token_len(&line[start..])),
+        label
+    );
+    writeln!(f, "{:w$} | {}", "", underline.trim_end(), w = width)
+}
+
+pub fn source_line(pos: &Pos) -> Option<String> {
+    let src = fs::read_to_string(&pos.2).ok()?;
+    src.lines().nth(pos.0.checked_sub(1)?).map(|x| x.trim_end().to_string())
+}
+
+fn token_len(rest: &str) -> usize {
+    let len = if let Some(string) = rest.strip_prefix('"') {
+        string.find('"').map_or(rest.len(), |x| x + 2)
+    } else {
+        rest.find(' ').unwrap_or(rest.len())
+    };
+    rest[..len].chars().count().max(1)
+}
@@ -1,22 +1,128 @@
-use crate::{ops, report_err, Runtime};
-use std::io::{stdin, stdout, Write};
+use crate::check::op_name;
+use crate::diagnostic::{Diagnostic, E_CAST, E_DEF, E_ARITH, E_FUNC, E_IO, E_MEM, E_RANGE, E_STACK, E_TYPE, E_UNKNOWN};
+use crate::{ops, report_err, FileHandle, Runtime};
+use std::io::{stdin, stdout, BufRead, Read, Write};
 
 macro_rules! check_stack_min {
     ($ctx:expr, $tok:expr, $min_len:expr, $err_msg:expr) => {
         if $ctx.stack.len() < $min_len {
-            report_err!($tok.pos, $err_msg)
+            report_err!(E_STACK, $tok.pos, "{}", $err_msg)
         }
     };
 }
 
+// Regning som går rundt eller stopper ved grensene til typen i stedet for å gi en feil.
+macro_rules! fixed_arith {
+    ($op:expr, $x:expr, $y:expr) => {
+        match $op {
+            ops::Operator::AddWrap => $x.wrapping_add($y),
+            ops::Operator::SubWrap => $x.wrapping_sub($y),
+            ops::Operator::MultWrap => $x.wrapping_mul($y),
+            ops::Operator::AddSat => $x.saturating_add($y),
+            ops::Operator::SubSat => $x.saturating_sub($y),
+            _ => $x.saturating_mul($y),
+        }
+    };
+}
+
+macro_rules! check_live {
+    ($ctx:expr, $tok:expr, $val:expr) => {
+        if let ops::Value::Str(_) = $val {
+            if $ctx.read_str($val).is_none() {
+                report_err!(E_MEM, $tok.pos, "Strengen peker til frigjort minne")
+            }
+        }
+    };
+}
+
+// Blir kalt før og etter hver instruksjon som kjøres, med indeksen til instruksjonen.
+pub trait Hook {
+    fn before(&mut self, _ctx: &Runtime, _prg: &[ops::Instruction], _i: usize) {}
+    fn after(&mut self, _ctx: &Runtime, _prg: &[ops::Instruction], _i: usize) {}
+    // når programmet er ferdig, stoppet med 'avslutt' eller stoppet med en feil
+    fn finish(&mut self, _ctx: &Runtime, _prg: &[ops::Instruction]) {}
+    // en avslutnings kode hvis programmet skal stoppes før neste instruksjon
+    fn stop(&self) -> Option<u8> {
+        None
+    }
+}
+
+pub struct NoHook;
+
+impl Hook for NoHook {}
+
+impl Hook for Vec<Box<dyn Hook>> {
+    fn before(&mut self, ctx: &Runtime, prg: &[ops::Instruction], i: usize) {
+        for hook in self.iter_mut() {
+            hook.before(ctx, prg, i);
+            // hookene etter skal ikke se en instruksjon som ikke blir kjørt
+            if hook.stop().is_some() {
+                break;
+            }
+        }
+    }
+
+    fn after(&mut self, ctx: &Runtime, prg: &[ops::Instruction], i: usize) {
+        for hook in self.iter_mut() {
+            hook.after(ctx, prg, i)
+        }
+    }
+
+    fn finish(&mut self, ctx: &Runtime, prg: &[ops::Instruction]) {
+        for hook in self.iter_mut() {
+            hook.finish(ctx, prg)
+        }
+    }
+
+    fn stop(&self) -> Option<u8> {
+        self.iter().find_map(|hook| hook.stop())
+    }
+}
+
 pub fn execute(
     ctx: &mut Runtime,
-    prg: &Vec<ops::Instruction>,
-) -> Result<u8, (&'static str, ops::Pos)> {
-    let mut i = 0;
+    prg: &[ops::Instruction],
+) -> Result<u8, Diagnostic> {
+    execute_from(ctx, prg, 0)
+}
+
+// Kjører `prg` fra instruksjon `start`. Brukes av REPL-en, der nye linjer legges
+// etter det som allerede er kjørt.
+pub fn execute_from(
+    ctx: &mut Runtime,
+    prg: &[ops::Instruction],
+    start: usize,
+) -> Result<u8, Diagnostic> {
+    execute_with(ctx, prg, start, &mut NoHook)
+}
+
+pub fn execute_with<H: Hook>(
+    ctx: &mut Runtime,
+    prg: &[ops::Instruction],
+    start: usize,
+    hook: &mut H,
+) -> Result<u8, Diagnostic> {
+    let res = run(ctx, prg, start, hook).map_err(|e| e.with_calls(ctx.call_trace(prg)));
+    hook.finish(ctx, prg);
+    res
+}
+
+fn run<H: Hook>(
+    ctx: &mut Runtime,
+    prg: &[ops::Instruction],
+    start: usize,
+    hook: &mut H,
+) -> Result<u8, Diagnostic> {
+    let mut i = start;
     while i < prg.len() {
         // println!("{}", ctx.current_scope);
         let token = &prg[i];
+        let current = i;
+        hook.before(ctx, prg, current);
+        if let Some(code) = hook.stop() {
+            let _ = stdout().flush();
+            return Ok(code);
+        }
 
         match token.op {
             ops::Operator::Literal => ctx.push(token.val.clone().unwrap()),
@@ -30,38 +136,51 @@ pub fn execute(
 
                 let b = ctx.pop().unwrap();
                 let a = ctx.pop().unwrap();
+                check_live!(ctx, token, &a);
+                check_live!(ctx, token, &b);
 
                 match (&a, &b) {
-                    (ops::Value::Int(x), ops::Value::Int(y)) => ctx.push(ops::Value::Int(x + y)),
-                    (ops::Value::Byte(x), ops::Value::Byte(y)) => ctx.push(ops::Value::Byte(x + y)),
-                    (ops::Value::Ptr(x), ops::Value::Int(y)) => {
-                        ctx.push(ops::Value::Ptr((x.0 + *y as usize, x.1, x.2)))
-                    }
-                    (ops::Value::Int(y), ops::Value::Ptr(x)) => {
-                        ctx.push(ops::Value::Ptr((x.0 + *y as usize, x.1, x.2)))
+                    (ops::Value::Int(x), ops::Value::Int(y)) => match x.checked_add(*y) {
+                        Some(res) => ctx.push(ops::Value::Int(res)),
+                        None => report_err!(E_ARITH, token.pos, "'{} + {}' er utenfor 'Helt', som går fra {} til {}", x, y, i32::MIN, i32::MAX),
+                    },
+                    (ops::Value::Int64(x), ops::Value::Int64(y)) => match x.checked_add(*y) {
+                        Some(res) => ctx.push(ops::Value::Int64(res)),
+                        None => report_err!(E_ARITH, token.pos, "'{} + {}' er utenfor 'Helt64', som går fra {} til {}", x, y, i64::MIN, i64::MAX),
+                    },
+                    (ops::Value::Byte(x), ops::Value::Byte(y)) => match x.checked_add(*y) {
+                        Some(res) => ctx.push(ops::Value::Byte(res)),
+                        None => report_err!(E_ARITH, token.pos, "'{:#04x} + {:#04x}' er utenfor 'Byte', som går fra 0 til 255", x, y),
+                    },
+                    (ops::Value::Ptr(x), ops::Value::Int(y))
+                    | (ops::Value::Int(y), ops::Value::Ptr(x)) => {
+                        let offset = (*y as isize) * ctx.size_of(&x.typ) as isize;
+                        let Some(new_ptr) = x.offset(offset) else {
+                            report_err!(E_MEM, token.pos, "Pekeren er utenfor grensene til [{}; {}]", x.typ, x.len);
+                        };
+                        ctx.push(ops::Value::Ptr(new_ptr))
                     }
                     (ops::Value::Float(x), ops::Value::Float(y)) => {
                         ctx.push(ops::Value::Float(x + y))
                     }
+                    (ops::Value::Float64(x), ops::Value::Float64(y)) => {
+                        ctx.push(ops::Value::Float64(x + y))
+                    }
                     (ops::Value::Str(x), ops::Value::Str(y)) => {
                         let s1: String = ctx.read_str(&ops::Value::Str(*x)).unwrap().clone();
                         let s2: String = ctx.read_str(&ops::Value::Str(*y)).unwrap().clone();
 
-                        let new_str = s1 + &s2;
-
-                        let res = ctx.write(&crate::parse::parse_char(&new_str));
-                        ctx.push(ops::Value::Str(res))
+                        let res = ctx.write_str(&(s1 + &s2));
+                        ctx.push(res)
                     }
                     (ops::Value::Str(x), ops::Value::Char(y)) => {
                         let s1: String = ctx.read_str(&ops::Value::Str(*x)).unwrap().clone();
 
-                        let new_str = s1 + &y.to_string();
-
-                        let res = ctx.write(&crate::parse::parse_char(&new_str));
-                        ctx.push(ops::Value::Str(res))
+                        let res = ctx.write_str(&(s1 + &y.to_string()));
+                        ctx.push(res)
                     }
                     (_, _) => {
-                        report_err!(token.pos, "'{} + {}' er ikke støttet", a, b);
+                        report_err!(E_TYPE, token.pos, "'{} + {}' er ikke støttet", a, b);
                     }
                 }
             }
@@ -77,19 +196,34 @@ pub fn execute(
                 let a = ctx.pop().unwrap();
 
                 match (&a, &b) {
-                    (ops::Value::Int(x), ops::Value::Int(y)) => ctx.push(ops::Value::Int(x - y)),
-                    (ops::Value::Byte(x), ops::Value::Byte(y)) => ctx.push(ops::Value::Byte(x - y)),
-                    (ops::Value::Ptr(x), ops::Value::Int(y)) => {
-                        ctx.push(ops::Value::Ptr((x.0 - *y as usize, x.1, x.2)))
-                    }
-                    (ops::Value::Int(y), ops::Value::Ptr(x)) => {
-                        ctx.push(ops::Value::Ptr((x.0 - *y as usize, x.1, x.2)))
+                    (ops::Value::Int(x), ops::Value::Int(y)) => match x.checked_sub(*y) {
+                        Some(res) => ctx.push(ops::Value::Int(res)),
+                        None => report_err!(E_ARITH, token.pos, "'{} - {}' er utenfor 'Helt', som går fra {} til {}", x, y, i32::MIN, i32::MAX),
+                    },
+                    (ops::Value::Int64(x), ops::Value::Int64(y)) => match x.checked_sub(*y) {
+                        Some(res) => ctx.push(ops::Value::Int64(res)),
+                        None => report_err!(E_ARITH, token.pos, "'{} - {}' er utenfor 'Helt64', som går fra {} til {}", x, y, i64::MIN, i64::MAX),
+                    },
+                    (ops::Value::Byte(x), ops::Value::Byte(y)) => match x.checked_sub(*y) {
+                        Some(res) => ctx.push(ops::Value::Byte(res)),
+                        None => report_err!(E_ARITH, token.pos, "'{:#04x} - {:#04x}' er utenfor 'Byte', som går fra 0 til 255", x, y),
+                    },
+                    (ops::Value::Ptr(x), ops::Value::Int(y))
+                    | (ops::Value::Int(y), ops::Value::Ptr(x)) => {
+                        let offset = -(*y as isize) * ctx.size_of(&x.typ) as isize;
+                        let Some(new_ptr) = x.offset(offset) else {
+                            report_err!(E_MEM, token.pos, "Pekeren er utenfor grensene til [{}; {}]", x.typ, x.len);
+                        };
+                        ctx.push(ops::Value::Ptr(new_ptr))
                     }
                     (ops::Value::Float(x), ops::Value::Float(y)) => {
                         ctx.push(ops::Value::Float(x - y))
                     }
+                    (ops::Value::Float64(x), ops::Value::Float64(y)) => {
+                        ctx.push(ops::Value::Float64(x - y))
+                    }
                     (_, _) => {
-                        report_err!(token.pos, "'{} - {}' er ikke støttet", a, b);
+                        report_err!(E_TYPE, token.pos, "'{} - {}' er ikke støttet", a, b);
                     }
                 }
             }
@@ -105,13 +239,26 @@ pub fn execute(
                 let a = ctx.pop().unwrap();
 
                 match (&a, &b) {
-                    (ops::Value::Int(x), ops::Value::Int(y)) => ctx.push(ops::Value::Int(x * y)),
-                    (ops::Value::Byte(x), ops::Value::Byte(y)) => ctx.push(ops::Value::Byte(x * y)),
+                    (ops::Value::Int(x), ops::Value::Int(y)) => match x.checked_mul(*y) {
+                        Some(res) => ctx.push(ops::Value::Int(res)),
+                        None => report_err!(E_ARITH, token.pos, "'{} * {}' er utenfor 'Helt', som går fra {} til {}", x, y, i32::MIN, i32::MAX),
+                    },
+                    (ops::Value::Int64(x), ops::Value::Int64(y)) => match x.checked_mul(*y) {
+                        Some(res) => ctx.push(ops::Value::Int64(res)),
+                        None => report_err!(E_ARITH, token.pos, "'{} * {}' er utenfor 'Helt64', som går fra {} til {}", x, y, i64::MIN, i64::MAX),
+                    },
+                    (ops::Value::Byte(x), ops::Value::Byte(y)) => match x.checked_mul(*y) {
+                        Some(res) => ctx.push(ops::Value::Byte(res)),
+                        None => report_err!(E_ARITH, token.pos, "'{:#04x} * {:#04x}' er utenfor 'Byte', som går fra 0 til 255", x, y),
+                    },
                     (ops::Value::Float(x), ops::Value::Float(y)) => {
                         ctx.push(ops::Value::Float(x * y))
                     }
+                    (ops::Value::Float64(x), ops::Value::Float64(y)) => {
+                        ctx.push(ops::Value::Float64(x * y))
+                    }
                     (_, _) => {
-                        report_err!(token.pos, "'{} * {}' er ikke støttet", a, b);
+                        report_err!(E_TYPE, token.pos, "'{} * {}' er ikke støttet", a, b);
                     }
                 }
             }
@@ -127,9 +274,30 @@ pub fn execute(
                 let a = ctx.pop().unwrap();
 
                 match (&a, &b) {
+                    (ops::Value::Int(_), ops::Value::Int(0))
+                    | (ops::Value::Int64(_), ops::Value::Int64(0))
+                    | (ops::Value::Byte(_), ops::Value::Byte(0)) => {
+                        report_err!(E_ARITH, token.pos, "Kan ikke dele på null");
+                    }
+                    (ops::Value::Float(_), ops::Value::Float(y)) if *y == 0.0 => {
+                        report_err!(E_ARITH, token.pos, "Kan ikke dele på null");
+                    }
+                    (ops::Value::Float64(_), ops::Value::Float64(y)) if *y == 0.0 => {
+                        report_err!(E_ARITH, token.pos, "Kan ikke dele på null");
+                    }
                     (ops::Value::Int(x), ops::Value::Int(y)) => {
+                        let Some(res) = x.checked_div(*y) else {
+                            report_err!(E_ARITH, token.pos, "'{} / {}' er utenfor 'Helt', som går fra {} til {}", x, y, i32::MIN, i32::MAX);
+                        };
                         ctx.push(ops::Value::Int(x % y));
-                        ctx.push(ops::Value::Int(x / y))
+                        ctx.push(ops::Value::Int(res))
+                    }
+                    (ops::Value::Int64(x), ops::Value::Int64(y)) => {
+                        let Some(res) = x.checked_div(*y) else {
+                            report_err!(E_ARITH, token.pos, "'{} / {}' er utenfor 'Helt64', som går fra {} til {}", x, y, i64::MIN, i64::MAX);
+                        };
+                        ctx.push(ops::Value::Int64(x % y));
+                        ctx.push(ops::Value::Int64(res))
                     }
                     (ops::Value::Byte(x), ops::Value::Byte(y)) => {
                         ctx.push(ops::Value::Byte(x % y));
@@ -139,8 +307,44 @@ pub fn execute(
                         ctx.push(ops::Value::Float(x % y));
                         ctx.push(ops::Value::Float(x / y))
                     }
+                    (ops::Value::Float64(x), ops::Value::Float64(y)) => {
+                        ctx.push(ops::Value::Float64(x % y));
+                        ctx.push(ops::Value::Float64(x / y))
+                    }
+                    (_, _) => {
+                        report_err!(E_TYPE, token.pos, "'{} / {}' er ikke støttet", a, b);
+                    }
+                }
+            }
+            ops::Operator::AddWrap
+            | ops::Operator::SubWrap
+            | ops::Operator::MultWrap
+            | ops::Operator::AddSat
+            | ops::Operator::SubSat
+            | ops::Operator::MultSat => {
+                let name = op_name(token.op);
+                check_stack_min!(
+                    ctx,
+                    token,
+                    2,
+                    format!("'{}' operator krever minst 2 argumenter av samme type", name)
+                );
+
+                let b = ctx.pop().unwrap();
+                let a = ctx.pop().unwrap();
+
+                match (&a, &b) {
+                    (ops::Value::Int(x), ops::Value::Int(y)) => {
+                        ctx.push(ops::Value::Int(fixed_arith!(token.op, x, *y)))
+                    }
+                    (ops::Value::Byte(x), ops::Value::Byte(y)) => {
+                        ctx.push(ops::Value::Byte(fixed_arith!(token.op, x, *y)))
+                    }
+                    (ops::Value::Int64(x), ops::Value::Int64(y)) => {
+                        ctx.push(ops::Value::Int64(fixed_arith!(token.op, x, *y)))
+                    }
                     (_, _) => {
-                        report_err!(token.pos, "'{} / {}' er ikke støttet", a, b);
+                        report_err!(E_TYPE, token.pos, "'{} {} {}' er ikke støttet", a, name, b);
                     }
                 }
             }
@@ -148,37 +352,254 @@ pub fn execute(
                 check_stack_min!(ctx, token, 1, "'skriv' operator krever minst 1 argument");
 
                 let print_val = ctx.pop().unwrap();
+                check_live!(ctx, token, &print_val);
                 print!("{}", print_val.to_string(ctx))
             }
             ops::Operator::PrintLn => {
                 check_stack_min!(ctx, token, 1, "'skrivnl' operator krever minst 1 argument");
 
                 let print_val = ctx.pop().unwrap();
-                print!("{}\n", print_val.to_string(ctx))
+                check_live!(ctx, token, &print_val);
+                println!("{}", print_val.to_string(ctx))
             }
             ops::Operator::Input => {
                 let print_value = ctx.pop();
 
                 if let Some(x) = print_value {
+                    check_live!(ctx, token, &x);
                     print!("{}", x.to_string(ctx))
                 }
 
+                let _ = stdout().flush();
                 let mut s = String::new();
-
+                if let Err(e) = stdin().read_line(&mut s) {
+                    report_err!(E_IO, token.pos, "Kunne ikke lese fra stdin: {}", e);
+                }
+                trim_newline(&mut s);
+                let res = ctx.write_str(&s);
+                ctx.push(res);
+            }
+            ops::Operator::InputLine => {
                 let _ = stdout().flush();
-                stdin()
-                    .read_line(&mut s)
-                    .expect("Did not enter a correct string");
-                if let Some('\n') = s.chars().next_back() {
-                    s.pop();
+                let mut s = String::new();
+                let read = match stdin().read_line(&mut s) {
+                    Ok(x) => x,
+                    Err(e) => report_err!(E_IO, token.pos, "Kunne ikke lese fra stdin: {}", e),
+                };
+                trim_newline(&mut s);
+                let res = ctx.write_str(&s);
+                ctx.push(res);
+                ctx.push(ops::Value::Bool(read > 0))
+            }
+            ops::Operator::InputAll => {
+                let mut s = String::new();
+                if let Err(e) = stdin().read_to_string(&mut s) {
+                    report_err!(E_IO, token.pos, "Kunne ikke lese fra stdin: {}", e);
                 }
-                if let Some('\r') = s.chars().next_back() {
-                    s.pop();
+                let res = ctx.write_str(&s);
+                ctx.push(res)
+            }
+            ops::Operator::InputChar => {
+                let _ = stdout().flush();
+                match read_char(&mut stdin().lock()) {
+                    Ok(x) => {
+                        ctx.push(ops::Value::Char(x.unwrap_or('\0')));
+                        ctx.push(ops::Value::Bool(x.is_some()))
+                    }
+                    Err(e) => report_err!(E_IO, token.pos, "Kunne ikke lese fra stdin: {}", e),
+                }
+            }
+            ops::Operator::Args => ctx.push(ops::Value::Int(ctx.args.len() as i32)),
+            ops::Operator::Arg => {
+                check_stack_min!(ctx, token, 1, "'argument' operator krever minst 1 argument");
+                let ops::Value::Int(x) = ctx.pop().unwrap() else {
+                    report_err!(E_TYPE, token.pos, "'argument' krever en 'Helt'");
+                };
+                let Some(arg) = usize::try_from(x).ok().and_then(|x| ctx.args.get(x)).cloned() else {
+                    report_err!(E_RANGE, token.pos, "Argument {} finnes ikke, programmet fikk {} argumenter", x, ctx.args.len());
+                };
+                let res = ctx.write_str(&arg);
+                ctx.push(res)
+            }
+            ops::Operator::Env => {
+                check_stack_min!(ctx, token, 1, "'miljø' operator krever minst 1 argument");
+                let name = ctx.pop().unwrap();
+                check_live!(ctx, token, &name);
+                let ops::Value::Str(_) = name else {
+                    report_err!(E_TYPE, token.pos, "'miljø' krever en 'Str'");
+                };
+                let val = std::env::var(ctx.read_str(&name).unwrap());
+                let res = ctx.write_str(val.as_deref().unwrap_or(""));
+                ctx.push(res);
+                ctx.push(ops::Value::Bool(val.is_ok()))
+            }
+            ops::Operator::Len => {
+                check_stack_min!(ctx, token, 1, "'lengde' operator krever minst 1 argument");
+                let x = pop_text(ctx, token)?;
+                ctx.push(ops::Value::Int(x.chars().count() as i32))
+            }
+            ops::Operator::CharAt => {
+                check_stack_min!(ctx, token, 2, "'ved' operator krever en streng og en indeks");
+                let i = ctx.pop().unwrap();
+                let x = pop_text(ctx, token)?;
+                let i = index(token, &i)?;
+                let Some(c) = x.chars().nth(i) else {
+                    report_err!(E_RANGE, token.pos, "Indeks {} er utenfor strengen med lengde {}", i, x.chars().count());
+                };
+                ctx.push(ops::Value::Char(c))
+            }
+            ops::Operator::Substr => {
+                check_stack_min!(ctx, token, 3, "'delstr' operator krever en streng, en start og en lengde");
+                let len = ctx.pop().unwrap();
+                let start = ctx.pop().unwrap();
+                let x = pop_text(ctx, token)?;
+                let (start, len) = (index(token, &start)?, index(token, &len)?);
+                let count = x.chars().count();
+                if start + len > count {
+                    report_err!(E_RANGE, token.pos, "{} bokstaver fra {} er utenfor strengen med lengde {}", len, start, count);
+                }
+                let res = ctx.write_str(&x.chars().skip(start).take(len).collect::<String>());
+                ctx.push(res)
+            }
+            ops::Operator::Find => {
+                check_stack_min!(ctx, token, 2, "'finn' operator krever en streng og noe å finne");
+                let pattern = pop_text(ctx, token)?;
+                let x = pop_text(ctx, token)?;
+                let found = x.find(&pattern).map_or(-1, |j| x[..j].chars().count() as i32);
+                ctx.push(ops::Value::Int(found))
+            }
+            ops::Operator::Replace => {
+                check_stack_min!(ctx, token, 3, "'erstatt' operator krever en streng, et mønster og en erstatning");
+                let to = pop_text(ctx, token)?;
+                let from = pop_text(ctx, token)?;
+                let x = pop_text(ctx, token)?;
+                if from.is_empty() {
+                    report_err!(E_RANGE, token.pos, "'erstatt' kan ikke erstatte en tom streng");
+                }
+                let res = ctx.write_str(&x.replace(&from, &to));
+                ctx.push(res)
+            }
+            ops::Operator::Split => {
+                check_stack_min!(ctx, token, 2, "'del' operator krever en streng og et skilletegn");
+                let sep = pop_text(ctx, token)?;
+                let x = pop_text(ctx, token)?;
+                if sep.is_empty() {
+                    report_err!(E_RANGE, token.pos, "'del' kan ikke dele på en tom streng");
+                }
+                let parts: Vec<ops::Value> = x.split(&sep).map(|part| ctx.write_str(part)).collect();
+                let (ptr, len, id) = ctx.write(&parts);
+                ctx.push(ops::Value::Ptr(ops::Pointer::new(ptr, len, ops::TypeLiteral::Str, 1, id)));
+                ctx.push(ops::Value::Int(len as i32))
+            }
+            ops::Operator::Upper | ops::Operator::Lower if matches!(ctx.stack.last(), Some(ops::Value::Char(_))) => {
+                let Some(ops::Value::Char(x)) = ctx.pop() else { unreachable!() };
+                // bokstaver som blir til flere, som 'ß' til "SS", blir ikke endret
+                let mut res = match token.op {
+                    ops::Operator::Upper => x.to_uppercase().collect::<Vec<char>>(),
+                    _ => x.to_lowercase().collect(),
+                };
+                ctx.push(ops::Value::Char(if res.len() == 1 { res.remove(0) } else { x }))
+            }
+            ops::Operator::IsDigit | ops::Operator::IsLetter | ops::Operator::IsSpace => {
+                check_stack_min!(ctx, token, 1, format!("'{}' operator krever minst 1 argument", op_name(token.op)));
+                let ops::Value::Char(x) = ctx.pop().unwrap() else {
+                    report_err!(E_TYPE, token.pos, "'{}' krever en 'Bokst'", op_name(token.op));
+                };
+                let res = match token.op {
+                    ops::Operator::IsDigit => x.is_ascii_digit(),
+                    ops::Operator::IsLetter => x.is_alphabetic(),
+                    _ => x.is_whitespace(),
+                };
+                ctx.push(ops::Value::Bool(res))
+            }
+            ops::Operator::Trim | ops::Operator::Upper | ops::Operator::Lower => {
+                check_stack_min!(ctx, token, 1, format!("'{}' operator krever minst 1 argument", op_name(token.op)));
+                let x = pop_text(ctx, token)?;
+                let res = match token.op {
+                    ops::Operator::Trim => x.trim().to_string(),
+                    ops::Operator::Upper => x.to_uppercase(),
+                    _ => x.to_lowercase(),
+                };
+                let res = ctx.write_str(&res);
+                ctx.push(res)
+            }
+            ops::Operator::StartsWith | ops::Operator::EndsWith => {
+                check_stack_min!(ctx, token, 2, format!("'{}' operator krever minst 2 argumenter", op_name(token.op)));
+                let pattern = pop_text(ctx, token)?;
+                let x = pop_text(ctx, token)?;
+                let res = match token.op {
+                    ops::Operator::StartsWith => x.starts_with(&pattern),
+                    _ => x.ends_with(&pattern),
+                };
+                ctx.push(ops::Value::Bool(res))
+            }
+            ops::Operator::Open => {
+                check_stack_min!(ctx, token, 2, "'åpne' operator krever en sti og en modus");
+                let mode = ctx.pop().unwrap();
+                let path = ctx.pop().unwrap();
+                check_live!(ctx, token, &path);
+                check_live!(ctx, token, &mode);
+                let (Some(path), Some(mode)) = (ctx.read_str(&path), ctx.read_str(&mode)) else {
+                    report_err!(E_TYPE, token.pos, "'åpne' krever en sti og en modus som 'Str', fant {} og {}", path, mode);
+                };
+                match ctx.open(&path, &mode) {
+                    Ok(x) => ctx.push(x),
+                    Err(e) => report_err!(E_IO, token.pos, "Kunne ikke åpne '{}': {}", path, e),
+                }
+            }
+            ops::Operator::ReadLine => {
+                check_stack_min!(ctx, token, 1, "'leslinje' operator krever minst 1 argument");
+                let val = ctx.pop().unwrap();
+                let FileHandle::Read(reader) = file(ctx, token, &val)? else {
+                    report_err!(E_IO, token.pos, "Filen er ikke åpnet for lesing");
+                };
+                let mut line = String::new();
+                let read = match reader.read_line(&mut line) {
+                    Ok(x) => x,
+                    Err(e) => report_err!(E_IO, token.pos, "Kunne ikke lese fra filen: {}", e),
+                };
+                trim_newline(&mut line);
+                let res = ctx.write_str(&line);
+                ctx.push(res);
+                ctx.push(ops::Value::Bool(read > 0))
+            }
+            ops::Operator::ReadAll => {
+                check_stack_min!(ctx, token, 1, "'lesalt' operator krever minst 1 argument");
+                let val = ctx.pop().unwrap();
+                let FileHandle::Read(reader) = file(ctx, token, &val)? else {
+                    report_err!(E_IO, token.pos, "Filen er ikke åpnet for lesing");
+                };
+                let mut text = String::new();
+                if let Err(e) = reader.read_to_string(&mut text) {
+                    report_err!(E_IO, token.pos, "Kunne ikke lese fra filen: {}", e);
+                }
+                let res = ctx.write_str(&text);
+                ctx.push(res)
+            }
+            ops::Operator::WriteFile => {
+                check_stack_min!(ctx, token, 2, "'skrivfil' operator krever en verdi og en fil");
+                let val = ctx.pop().unwrap();
+                let data = ctx.pop().unwrap();
+                check_live!(ctx, token, &data);
+                let text = data.to_string(ctx);
+                let FileHandle::Write(writer) = file(ctx, token, &val)? else {
+                    report_err!(E_IO, token.pos, "Filen er ikke åpnet for skriving");
+                };
+                if let Err(e) = writer.write_all(text.as_bytes()) {
+                    report_err!(E_IO, token.pos, "Kunne ikke skrive til filen: {}", e);
+                }
+            }
+            ops::Operator::Close => {
+                check_stack_min!(ctx, token, 1, "'lukk' operator krever minst 1 argument");
+                let val = ctx.pop().unwrap();
+                if let FileHandle::Write(writer) = file(ctx, token, &val)? {
+                    if let Err(e) = writer.flush() {
+                        report_err!(E_IO, token.pos, "Kunne ikke skrive til filen: {}", e);
+                    }
+                }
+                if let ops::Value::File(x) = val {
+                    ctx.files[x] = None
                 }
-
-                let unescaped_x = crate::parse::parse_char(&s);
-                let res = ctx.write(&unescaped_x);
-                ctx.push(ops::Value::Str(res));
             }
             ops::Operator::Not => {
                 check_stack_min!(ctx, token, 1, "'ikke' operator krever minst 1 argument");
@@ -189,8 +610,9 @@ pub fn execute(
                     ops::Value::Bool(x) => ctx.push(ops::Value::Bool(!x)),
                     ops::Value::Int(x) => ctx.push(ops::Value::Int(!x)),
                     ops::Value::Byte(x) => ctx.push(ops::Value::Byte(!x)),
+                    ops::Value::Int64(x) => ctx.push(ops::Value::Int64(!x)),
                     _ => {
-                        report_err!(token.pos, "'ikke {}' er ikke støttet", b);
+                        report_err!(E_TYPE, token.pos, "'ikke {}' er ikke støttet", b);
                     }
                 }
             }
@@ -211,8 +633,9 @@ pub fn execute(
                     }
                     (ops::Value::Int(x), ops::Value::Int(y)) => ctx.push(ops::Value::Int(x & y)),
                     (ops::Value::Byte(x), ops::Value::Byte(y)) => ctx.push(ops::Value::Byte(x & y)),
+                    (ops::Value::Int64(x), ops::Value::Int64(y)) => ctx.push(ops::Value::Int64(x & y)),
                     (_, _) => {
-                        report_err!(token.pos, "'{} og {}' er ikke støttet", a, b);
+                        report_err!(E_TYPE, token.pos, "'{} og {}' er ikke støttet", a, b);
                     }
                 }
             }
@@ -233,8 +656,101 @@ pub fn execute(
                     }
                     (ops::Value::Int(x), ops::Value::Int(y)) => ctx.push(ops::Value::Int(x | y)),
                     (ops::Value::Byte(x), ops::Value::Byte(y)) => ctx.push(ops::Value::Byte(x | y)),
+                    (ops::Value::Int64(x), ops::Value::Int64(y)) => ctx.push(ops::Value::Int64(x | y)),
                     (_, _) => {
-                        report_err!(token.pos, "'{} eller {}' er ikke støttet", a, b);
+                        report_err!(E_TYPE, token.pos, "'{} eller {}' er ikke støttet", a, b);
+                    }
+                }
+            }
+            ops::Operator::Xor => {
+                check_stack_min!(
+                    ctx,
+                    token,
+                    2,
+                    "'xeller' operator krever minst 2 argumenter av samme type"
+                );
+
+                let b = ctx.pop().unwrap();
+                let a = ctx.pop().unwrap();
+
+                match (&a, &b) {
+                    (ops::Value::Bool(x), ops::Value::Bool(y)) => ctx.push(ops::Value::Bool(x != y)),
+                    (ops::Value::Int(x), ops::Value::Int(y)) => ctx.push(ops::Value::Int(x ^ y)),
+                    (ops::Value::Byte(x), ops::Value::Byte(y)) => ctx.push(ops::Value::Byte(x ^ y)),
+                    (ops::Value::Int64(x), ops::Value::Int64(y)) => ctx.push(ops::Value::Int64(x ^ y)),
+                    (_, _) => {
+                        report_err!(E_TYPE, token.pos, "'{} xeller {}' er ikke støttet", a, b);
+                    }
+                }
+            }
+            ops::Operator::Shl | ops::Operator::Shr => {
+                let name = op_name(token.op);
+                check_stack_min!(
+                    ctx,
+                    token,
+                    2,
+                    format!("'{}' operator krever en 'Helt' eller 'Byte' og en 'Helt'", name)
+                );
+
+                let b = ctx.pop().unwrap();
+                let a = ctx.pop().unwrap();
+
+                // `checked_shl` og `checked_shr` gir bare None når det skyves med
+                // minst like mange bits som typen har
+                let shifted = match (&a, &b, token.op) {
+                    (ops::Value::Int(x), ops::Value::Int(y), ops::Operator::Shl) => {
+                        u32::try_from(*y).ok().and_then(|y| x.checked_shl(y)).map(ops::Value::Int)
+                    }
+                    (ops::Value::Int(x), ops::Value::Int(y), _) => {
+                        u32::try_from(*y).ok().and_then(|y| x.checked_shr(y)).map(ops::Value::Int)
+                    }
+                    (ops::Value::Byte(x), ops::Value::Int(y), ops::Operator::Shl) => {
+                        u32::try_from(*y).ok().and_then(|y| x.checked_shl(y)).map(ops::Value::Byte)
+                    }
+                    (ops::Value::Byte(x), ops::Value::Int(y), _) => {
+                        u32::try_from(*y).ok().and_then(|y| x.checked_shr(y)).map(ops::Value::Byte)
+                    }
+                    (ops::Value::Int64(x), ops::Value::Int(y), ops::Operator::Shl) => {
+                        u32::try_from(*y).ok().and_then(|y| x.checked_shl(y)).map(ops::Value::Int64)
+                    }
+                    (ops::Value::Int64(x), ops::Value::Int(y), _) => {
+                        u32::try_from(*y).ok().and_then(|y| x.checked_shr(y)).map(ops::Value::Int64)
+                    }
+                    (_, _, _) => {
+                        report_err!(E_TYPE, token.pos, "'{} {} {}' er ikke støttet", a, name, b);
+                    }
+                };
+                match shifted {
+                    Some(x) => ctx.push(x),
+                    None => {
+                        let bits = match a {
+                            ops::Value::Byte(_) => 8,
+                            ops::Value::Int64(_) => 64,
+                            _ => 32,
+                        };
+                        let ops::Value::Int(y) = b else { unreachable!() };
+                        report_err!(
+                            E_ARITH,
+                            token.pos,
+                            "Kan ikke skyve en '{}' med {} bits, det må være fra 0 til {}",
+                            a,
+                            y,
+                            bits - 1
+                        );
+                    }
+                }
+            }
+            ops::Operator::PopCount => {
+                check_stack_min!(ctx, token, 1, "'bitantall' operator krever minst 1 argument");
+
+                let a = ctx.pop().unwrap();
+
+                match a {
+                    ops::Value::Int(x) => ctx.push(ops::Value::Int(x.count_ones() as i32)),
+                    ops::Value::Byte(x) => ctx.push(ops::Value::Int(x.count_ones() as i32)),
+                    ops::Value::Int64(x) => ctx.push(ops::Value::Int(x.count_ones() as i32)),
+                    _ => {
+                        report_err!(E_TYPE, token.pos, "'bitantall {}' er ikke støttet", a);
                     }
                 }
             }
@@ -248,12 +764,16 @@ pub fn execute(
 
                 let b = ctx.pop().unwrap();
                 let a = ctx.pop().unwrap();
+                check_live!(ctx, token, &a);
+                check_live!(ctx, token, &b);
 
                 match (&a, &b) {
                     (ops::Value::Bool(x), ops::Value::Bool(y)) => {
                         ctx.push(ops::Value::Bool(x == y))
                     }
                     (ops::Value::Int(x), ops::Value::Int(y)) => ctx.push(ops::Value::Bool(x == y)),
+                    (ops::Value::Int64(x), ops::Value::Int64(y)) => ctx.push(ops::Value::Bool(x == y)),
+                    (ops::Value::Float64(x), ops::Value::Float64(y)) => ctx.push(ops::Value::Bool(x == y)),
                     (ops::Value::Byte(x), ops::Value::Byte(y)) => {
                         ctx.push(ops::Value::Bool(x == y))
                     }
@@ -269,9 +789,9 @@ pub fn execute(
                     (ops::Value::Char(x), ops::Value::Char(y)) => {
                         ctx.push(ops::Value::Bool(x == y))
                     }
-                    (x, ops::Value::TypeLiteral(y)) => ctx.push(ops::Value::Bool(x.eq(&y))),
+                    (x, ops::Value::TypeLiteral(y)) => ctx.push(ops::Value::Bool(x.eq(y))),
                     (_, _) => {
-                        report_err!(token.pos, "'{} = {}' er ikke støttet", a, b);
+                        report_err!(E_TYPE, token.pos, "'{} = {}' er ikke støttet", a, b);
                     }
                 }
             }
@@ -289,12 +809,21 @@ pub fn execute(
                 match (&a, &b) {
                     (ops::Value::Bool(x), ops::Value::Bool(y)) => ctx.push(ops::Value::Bool(x < y)),
                     (ops::Value::Int(x), ops::Value::Int(y)) => ctx.push(ops::Value::Bool(x < y)),
+                    (ops::Value::Int64(x), ops::Value::Int64(y)) => ctx.push(ops::Value::Bool(x < y)),
+                    (ops::Value::Float64(x), ops::Value::Float64(y)) => ctx.push(ops::Value::Bool(x < y)),
                     (ops::Value::Byte(x), ops::Value::Byte(y)) => ctx.push(ops::Value::Bool(x < y)),
                     (ops::Value::Float(x), ops::Value::Float(y)) => {
                         ctx.push(ops::Value::Bool(x < y))
                     }
+                    (ops::Value::Char(x), ops::Value::Char(y)) => ctx.push(ops::Value::Bool(x < y)),
+                    (ops::Value::Str(_), ops::Value::Str(_)) => {
+                        check_live!(ctx, token, &a);
+                        check_live!(ctx, token, &b);
+                        let res = ctx.read_str(&a) < ctx.read_str(&b);
+                        ctx.push(ops::Value::Bool(res))
+                    }
                     (_, _) => {
-                        report_err!(token.pos, "'{} < {}' er ikke støttet", a, b);
+                        report_err!(E_TYPE, token.pos, "'{} < {}' er ikke støttet", a, b);
                     }
                 }
             }
@@ -314,14 +843,23 @@ pub fn execute(
                         ctx.push(ops::Value::Bool(x <= y))
                     }
                     (ops::Value::Int(x), ops::Value::Int(y)) => ctx.push(ops::Value::Bool(x <= y)),
+                    (ops::Value::Int64(x), ops::Value::Int64(y)) => ctx.push(ops::Value::Bool(x <= y)),
+                    (ops::Value::Float64(x), ops::Value::Float64(y)) => ctx.push(ops::Value::Bool(x <= y)),
                     (ops::Value::Byte(x), ops::Value::Byte(y)) => {
                         ctx.push(ops::Value::Bool(x <= y))
                     }
                     (ops::Value::Float(x), ops::Value::Float(y)) => {
                         ctx.push(ops::Value::Bool(x <= y))
                     }
+                    (ops::Value::Char(x), ops::Value::Char(y)) => ctx.push(ops::Value::Bool(x <= y)),
+                    (ops::Value::Str(_), ops::Value::Str(_)) => {
+                        check_live!(ctx, token, &a);
+                        check_live!(ctx, token, &b);
+                        let res = ctx.read_str(&a) <= ctx.read_str(&b);
+                        ctx.push(ops::Value::Bool(res))
+                    }
                     (_, _) => {
-                        report_err!(token.pos, "'{} <= {}' er ikke støttet", a, b);
+                        report_err!(E_TYPE, token.pos, "'{} <= {}' er ikke støttet", a, b);
                     }
                 }
             }
@@ -339,12 +877,21 @@ pub fn execute(
                 match (&a, &b) {
                     (ops::Value::Bool(x), ops::Value::Bool(y)) => ctx.push(ops::Value::Bool(x > y)),
                     (ops::Value::Int(x), ops::Value::Int(y)) => ctx.push(ops::Value::Bool(x > y)),
+                    (ops::Value::Int64(x), ops::Value::Int64(y)) => ctx.push(ops::Value::Bool(x > y)),
+                    (ops::Value::Float64(x), ops::Value::Float64(y)) => ctx.push(ops::Value::Bool(x > y)),
                     (ops::Value::Byte(x), ops::Value::Byte(y)) => ctx.push(ops::Value::Bool(x > y)),
                     (ops::Value::Float(x), ops::Value::Float(y)) => {
                         ctx.push(ops::Value::Bool(x > y))
                     }
+                    (ops::Value::Char(x), ops::Value::Char(y)) => ctx.push(ops::Value::Bool(x > y)),
+                    (ops::Value::Str(_), ops::Value::Str(_)) => {
+                        check_live!(ctx, token, &a);
+                        check_live!(ctx, token, &b);
+                        let res = ctx.read_str(&a) > ctx.read_str(&b);
+                        ctx.push(ops::Value::Bool(res))
+                    }
                     (_, _) => {
-                        report_err!(token.pos, "'{} > {}' er ikke støttet", a, b);
+                        report_err!(E_TYPE, token.pos, "'{} > {}' er ikke støttet", a, b);
                     }
                 }
             }
@@ -364,14 +911,23 @@ pub fn execute(
                         ctx.push(ops::Value::Bool(x >= y))
                     }
                     (ops::Value::Int(x), ops::Value::Int(y)) => ctx.push(ops::Value::Bool(x >= y)),
+                    (ops::Value::Int64(x), ops::Value::Int64(y)) => ctx.push(ops::Value::Bool(x >= y)),
+                    (ops::Value::Float64(x), ops::Value::Float64(y)) => ctx.push(ops::Value::Bool(x >= y)),
                     (ops::Value::Byte(x), ops::Value::Byte(y)) => {
                         ctx.push(ops::Value::Bool(x >= y))
                     }
                     (ops::Value::Float(x), ops::Value::Float(y)) => {
                         ctx.push(ops::Value::Bool(x >= y))
                     }
+                    (ops::Value::Char(x), ops::Value::Char(y)) => ctx.push(ops::Value::Bool(x >= y)),
+                    (ops::Value::Str(_), ops::Value::Str(_)) => {
+                        check_live!(ctx, token, &a);
+                        check_live!(ctx, token, &b);
+                        let res = ctx.read_str(&a) >= ctx.read_str(&b);
+                        ctx.push(ops::Value::Bool(res))
+                    }
                     (_, _) => {
-                        report_err!(token.pos, "'{} >= {}' er ikke støttet", a, b);
+                        report_err!(E_TYPE, token.pos, "'{} >= {}' er ikke støttet", a, b);
                     }
                 }
             }
@@ -383,6 +939,8 @@ pub fn execute(
                         ctx.def.remove(key);
                     }
                 }
+                let current_scope = ctx.current_scope;
+                ctx.vars.last_mut().unwrap().retain(|_, (_, _, scope)| *scope < current_scope);
                 ctx.current_scope -= 1;
                 if let Some(ptr) = token.arg {
                     match prg[ptr].op {
@@ -400,17 +958,14 @@ pub fn execute(
                             if let Some(key) = name {
                                 if let (Some(_), _) = ctx.def[key] {
                                     report_err!(
-                                        token.pos,
+                                        E_DEF, token.pos,
                                         "'{}' kan ikke omdefineres ettersom den er konstant",
                                         key
                                     );
                                 }
                                 ctx.def.insert(key.to_string(), (Some(val), ctx.current_scope));
                             } else {
-                                return Err((
-                                    "Kunne ikke finne valid 'konst' navn",
-                                    token.pos.clone(),
-                                ));
+                                report_err!(E_DEF, token.pos, "Kunne ikke finne valid 'konst' navn");
                             }
                         }
                         ops::Operator::Mem => {
@@ -422,56 +977,70 @@ pub fn execute(
                         );
 
                             let ops::Value::Int(len) = ctx.pop().unwrap() else {
-                            return Err((
-                                "Verdien på toppen av stabelen må være et positivt heltall",
-                                token.pos.clone(),
-                            ));
+                            report_err!(E_TYPE, token.pos, "Verdien på toppen av stabelen må være et positivt heltall");
                         };
                             if len <= 0 {
-                                return Err((
-                                "Verdien på toppen av stabelen må være et positivt heltall og kan ikke vær null eller mindre",
-                                token.pos.clone(),
-                            ));
+                                report_err!(E_TYPE, token.pos, "Verdien på toppen av stabelen må være et positivt heltall og kan ikke vær null eller mindre");
                             }
                             let ops::Value::TypeLiteral(typ) = ctx.pop().unwrap() else {
-                            return Err((
-                                "Verdien på toppen av stabelen må være en type",
-                                token.pos.clone(),
-                            ));
+                            report_err!(E_TYPE, token.pos, "Verdien på toppen av stabelen må være en type");
                         };
                             let name = &prg[ptr + 1].name;
 
                             if let Some(key) = name {
                                 if let (Some(_), _) = ctx.def[key] {
                                     report_err!(
-                                        token.pos,
+                                        E_DEF, token.pos,
                                         "'{}' kan ikke omdefineres ettersom den er konstant",
                                         key
                                     );
                                 }
 
-                                let res = ctx.write(&vec![ops::Value::Null; len as usize]);
-                                let result = (res.0, res.1, typ);
+                                let size = len as usize * ctx.size_of(&typ);
+                                let res = ctx.write(&vec![ops::Value::Null; size]);
+                                ctx.add_local(res.0, size);
+                                let result = ops::Pointer::new(res.0, len as usize, typ, ctx.size_of(&typ), res.2);
                                 ctx.def
                                     .insert(key.to_string(), (Some(ops::Value::Ptr(result)), ctx.current_scope));
                             } else {
-                                return Err((
-                                    "Kunne ikke finne valid 'konst' navn",
-                                    token.pos.clone(),
-                                ));
+                                report_err!(E_DEF, token.pos, "Kunne ikke finne valid 'konst' navn");
+                            }
+                        }
+                        ops::Operator::Var => {
+                            check_stack_min!(
+                                ctx,
+                                token,
+                                2,
+                                "'var' definisjon krever en type og en verdi på toppen av stabelen"
+                            );
+
+                            let val = ctx.pop().unwrap();
+                            let ops::Value::TypeLiteral(typ) = ctx.pop().unwrap() else {
+                                report_err!(E_TYPE, token.pos, "Verdien under toppen av stabelen må være en type");
+                            };
+                            if !val.eq(&typ) {
+                                report_err!(E_TYPE, token.pos, "Forventet {} men fant {}", typ, val);
                             }
+                            let Some(key) = &prg[ptr + 1].name else {
+                                report_err!(E_DEF, token.pos, "Kunne ikke finne valid 'var' navn");
+                            };
+                            ctx.vars
+                                .last_mut()
+                                .unwrap()
+                                .insert(key.to_string(), (val, typ, ctx.current_scope));
                         }
                         ops::Operator::Func => {
                             let func = &prg[ptr + 1];
-                            let Some(func_name) = &func.name else {report_err!(token.pos, "fant ikke funksjons navn");};
+                            let Some(func_name) = &func.name else {report_err!(E_FUNC, token.pos, "fant ikke funksjons navn");};
                             let (Some(ops::Value::FuncPtr(func_ptr)), _) = ctx.def[func_name].clone() else {
-                                report_err!(token.pos, "fant ikke funksjons navn");
-                            };
-                            let Some(res) = ctx.retur(&func_ptr) else {
-                                report_err!(token.pos, "ikke rette retur verdier for funksjon '{}'", func_name);
+                                report_err!(E_FUNC, token.pos, "fant ikke funksjons navn");
                             };
-
-                            i = res
+                            match ctx.retur(&func_ptr) {
+                                Ok(res) => i = res,
+                                Err(e) => {
+                                    report_err!(E_FUNC, token.pos, "{} funksjon '{}'", e, func_name);
+                                }
+                            }
                         }
                         ops::Operator::Let => (),
                         _ => {
@@ -488,6 +1057,8 @@ pub fn execute(
                         ctx.def.remove(key);
                     }
                 }
+                    let current_scope = ctx.current_scope;
+                    ctx.vars.last_mut().unwrap().retain(|_, (_, _, scope)| *scope < current_scope);
                     ctx.current_scope -= 1;
                     i = ptr;
                 }
@@ -499,9 +1070,7 @@ pub fn execute(
 
                 if let ops::Value::Bool(x) = con {
                     if x {
-                        i += 1;
                         ctx.current_scope += 1;
-                        continue;
                     } else {
                         let next_i = token.arg.unwrap();
                         if prg[next_i].op == ops::Operator::Else {
@@ -513,7 +1082,7 @@ pub fn execute(
                     }
                 } else {
                     report_err!(
-                        token.pos,
+                        E_TYPE, token.pos,
                         "'gjør {}' er ikke støttet. 'gjør' only takes Bool",
                         con
                     );
@@ -524,31 +1093,57 @@ pub fn execute(
                 let name = &prg[i + 1];
                 if name.op == ops::Operator::Word {
                     let Some(key) = &name.name else {
-                        report_err!(token.pos, "Kunne ikke finne navn");
+                        report_err!(E_DEF, token.pos, "Kunne ikke finne navn");
                     };
                     ctx.def.insert(key.to_string(), (None, ctx.current_scope));
                     ctx.current_scope += 1;
                 } else {
-                    report_err!(token.pos, "Kunne ikke finne navn til minne");
+                    report_err!(E_DEF, token.pos, "Kunne ikke finne navn til minne");
+                }
+                i += 1
+            }
+            ops::Operator::Var => {
+                let name = &prg[i + 1];
+                if name.op == ops::Operator::Word {
+                    ctx.current_scope += 1;
+                } else {
+                    report_err!(E_DEF, token.pos, "Kunne ikke finne navn til variabel");
                 }
                 i += 1
             }
+            ops::Operator::SetVar => {
+                check_stack_min!(ctx, token, 1, "'sett' operator krever minst 1 argument");
+
+                let name = &prg[i + 1];
+                let (ops::Operator::Word, Some(key)) = (name.op, &name.name) else {
+                    report_err!(E_DEF, token.pos, "'sett' forventer et variabel navn");
+                };
+                let val = ctx.pop().unwrap();
+                let Some((var, typ, _)) = ctx.get_var(key) else {
+                    report_err!(E_UNKNOWN, name.pos, "Ukjent variabel '{}'", key);
+                };
+                if !val.eq(typ) {
+                    report_err!(E_TYPE, token.pos, "Variabelen '{}' er {} men fikk {}", key, typ, val);
+                }
+                *var = val;
+                i += 1
+            }
             ops::Operator::Const => {
                 let name = &prg[i + 1];
                 if name.op == ops::Operator::Word {
                     let Some(key) = &name.name else {
-                        report_err!(token.pos, "Kunne ikke finne navn");
+                        report_err!(E_DEF, token.pos, "Kunne ikke finne navn");
                     };
                     ctx.def.insert(key.to_string(), (None, ctx.current_scope));
                     ctx.current_scope += 1;
                 } else {
-                    report_err!(token.pos, "Kunne ikke finne navn til konstant");
+                    report_err!(E_DEF, token.pos, "Kunne ikke finne navn til konstant");
                 }
                 i += 1
             }
             ops::Operator::Dup => {
-                if ctx.stack.len() < 1 {
-                    return Err(("'dup' operator krever minst 1 argument", token.pos.clone()));
+                if ctx.stack.is_empty() {
+                    report_err!(E_STACK, token.pos, "'dup' operator krever minst 1 argument");
                 }
 
                 let b = ctx.pop().unwrap();
@@ -596,10 +1191,28 @@ pub fn execute(
 
                 let typ = ctx.pop().unwrap();
                 let b = ctx.pop().unwrap();
+                check_live!(ctx, token, &b);
 
                 match (&typ, &b) {
                     (ops::Value::TypeLiteral(ops::TypeLiteral::Int), _) => match b {
-                        ops::Value::Float(x) => ctx.push(ops::Value::Int(x as i32)),
+                        ops::Value::Float(x) => {
+                            let Some(x) = float_to_int(x as f64) else {
+                                report_err!(E_CAST, token.pos, "{} er utenfor 'Helt', som går fra {} til {}", x, i32::MIN, i32::MAX);
+                            };
+                            ctx.push(ops::Value::Int(x))
+                        }
+                        ops::Value::Float64(x) => {
+                            let Some(x) = float_to_int(x) else {
+                                report_err!(E_CAST, token.pos, "{} er utenfor 'Helt', som går fra {} til {}", x, i32::MIN, i32::MAX);
+                            };
+                            ctx.push(ops::Value::Int(x))
+                        }
+                        ops::Value::Int64(x) => {
+                            let Ok(x) = i32::try_from(x) else {
+                                report_err!(E_CAST, token.pos, "{} er utenfor 'Helt', som går fra {} til {}", x, i32::MIN, i32::MAX);
+                            };
+                            ctx.push(ops::Value::Int(x))
+                        }
                         ops::Value::Bool(x) => {
                             if x {
                                 ctx.push(ops::Value::Int(1i32))
@@ -607,24 +1220,25 @@ pub fn execute(
                                 ctx.push(ops::Value::Int(0i32))
                             }
                         }
+                        ops::Value::Byte(x) => ctx.push(ops::Value::Int(x as i32)),
+                        ops::Value::Char(x) => ctx.push(ops::Value::Int(x as i32)),
                         ops::Value::Str(x) => {
                             if let Ok(new_x) =
                                 ctx.read_str(&ops::Value::Str(x)).unwrap().parse::<i32>()
                             {
                                 ctx.push(ops::Value::Int(new_x));
                             } else {
-                                return Err((
-                                    "Fikk ikke til å omgjøre til Helt",
-                                    token.pos.clone(),
-                                ));
+                                report_err!(E_CAST, token.pos, "Fikk ikke til å omgjøre til Helt");
                             }
                         }
                         _ => {
-                            report_err!(token.pos, "Kunne ikke omgjøre {} til {}", b, typ);
+                            report_err!(E_CAST, token.pos, "Kunne ikke omgjøre {} til {}", b, typ);
                         }
                     },
                     (ops::Value::TypeLiteral(ops::TypeLiteral::Float), _) => match b {
                         ops::Value::Int(x) => ctx.push(ops::Value::Float(x as f32)),
+                        ops::Value::Int64(x) => ctx.push(ops::Value::Float(x as f32)),
+                        ops::Value::Float64(x) => ctx.push(ops::Value::Float(x as f32)),
                         ops::Value::Bool(x) => {
                             if x {
                                 ctx.push(ops::Value::Float(1.))
@@ -638,14 +1252,53 @@ pub fn execute(
                             {
                                 ctx.push(ops::Value::Float(new_x));
                             } else {
-                                return Err((
-                                    "Fikk ikke til å omgjøre til Flyt",
-                                    token.pos.clone(),
-                                ));
+                                report_err!(E_CAST, token.pos, "Fikk ikke til å omgjøre til Flyt");
                             }
                         }
                         _ => {
-                            report_err!(token.pos, "Kunne ikke omgjøre {} til {}", b, typ);
+                            report_err!(E_CAST, token.pos, "Kunne ikke omgjøre {} til {}", b, typ);
+                        }
+                    },
+                    (ops::Value::TypeLiteral(ops::TypeLiteral::Int64), _) => match b {
+                        ops::Value::Int(x) => ctx.push(ops::Value::Int64(x as i64)),
+                        ops::Value::Float(x) => {
+                            let Some(x) = float_to_int(x as f64) else {
+                                report_err!(E_CAST, token.pos, "{} er utenfor 'Helt64', som går fra {} til {}", x, i64::MIN, i64::MAX);
+                            };
+                            ctx.push(ops::Value::Int64(x))
+                        }
+                        ops::Value::Float64(x) => {
+                            let Some(x) = float_to_int(x) else {
+                                report_err!(E_CAST, token.pos, "{} er utenfor 'Helt64', som går fra {} til {}", x, i64::MIN, i64::MAX);
+                            };
+                            ctx.push(ops::Value::Int64(x))
+                        }
+                        ops::Value::Bool(x) => ctx.push(ops::Value::Int64(x as i64)),
+                        ops::Value::Byte(x) => ctx.push(ops::Value::Int64(x as i64)),
+                        ops::Value::Char(x) => ctx.push(ops::Value::Int64(x as i64)),
+                        ops::Value::Str(_) => {
+                            let Ok(x) = ctx.read_str(&b).unwrap().parse::<i64>() else {
+                                report_err!(E_CAST, token.pos, "Fikk ikke til å omgjøre til Helt64");
+                            };
+                            ctx.push(ops::Value::Int64(x))
+                        }
+                        _ => {
+                            report_err!(E_CAST, token.pos, "Kunne ikke omgjøre {} til {}", b, typ);
+                        }
+                    },
+                    (ops::Value::TypeLiteral(ops::TypeLiteral::Float64), _) => match b {
+                        ops::Value::Int(x) => ctx.push(ops::Value::Float64(x as f64)),
+                        ops::Value::Int64(x) => ctx.push(ops::Value::Float64(x as f64)),
+                        ops::Value::Float(x) => ctx.push(ops::Value::Float64(x as f64)),
+                        ops::Value::Bool(x) => ctx.push(ops::Value::Float64(if x { 1. } else { 0. })),
+                        ops::Value::Str(_) => {
+                            let Ok(x) = ctx.read_str(&b).unwrap().parse::<f64>() else {
+                                report_err!(E_CAST, token.pos, "Fikk ikke til å omgjøre til Flyt64");
+                            };
+                            ctx.push(ops::Value::Float64(x))
+                        }
+                        _ => {
+                            report_err!(E_CAST, token.pos, "Kunne ikke omgjøre {} til {}", b, typ);
                         }
                     },
                     (ops::Value::TypeLiteral(ops::TypeLiteral::Str), _) => match b {
@@ -659,32 +1312,65 @@ pub fn execute(
                             let res = ctx.write(&new_x);
                             ctx.push(ops::Value::Str(res))
                         }
+                        ops::Value::Int64(_) | ops::Value::Float64(_) => {
+                            let res = ctx.write_str(&b.to_string(ctx));
+                            ctx.push(res)
+                        }
                         ops::Value::Bool(x) => {
                             let new_x = crate::parse::parse_char(&x.to_string());
                             let res = ctx.write(&new_x);
                             ctx.push(ops::Value::Str(res))
                         }
                         ops::Value::Ptr(x) => {
-                            if x.2 != ops::TypeLiteral::Char {
-                                report_err!(token.pos, "Forventet 'Bokst' fant '{:?} ", x.2);
+                            if x.typ != ops::TypeLiteral::Char {
+                                report_err!(E_CAST, token.pos, "Forventet 'Bokst' fant '{:?} ", x.typ);
                             }
-                            ctx.push(ops::Value::Str((x.0, x.1)))
+                            ctx.push(ops::Value::Str((x.ptr, x.remaining(), x.alloc)))
                         }
                         _ => {
-                            report_err!(token.pos, "Kunne ikke omgjøre {} til {}", b, typ);
+                            report_err!(E_CAST, token.pos, "Kunne ikke omgjøre {} til {}", b, typ);
+                        }
+                    },
+                    (ops::Value::TypeLiteral(ops::TypeLiteral::Byte), _) => match b {
+                        ops::Value::Int(x) => {
+                            let Ok(x) = u8::try_from(x) else {
+                                report_err!(E_CAST, token.pos, "{} er utenfor 'Byte', som går fra 0 til 255", x);
+                            };
+                            ctx.push(ops::Value::Byte(x))
+                        }
+                        ops::Value::Char(x) => {
+                            let Ok(x) = u8::try_from(x) else {
+                                report_err!(E_CAST, token.pos, "'{}' er utenfor 'Byte', som går fra 0 til 255", x);
+                            };
+                            ctx.push(ops::Value::Byte(x))
+                        }
+                        _ => {
+                            report_err!(E_CAST, token.pos, "Kunne ikke omgjøre {} til {}", b, typ);
+                        }
+                    },
+                    (ops::Value::TypeLiteral(ops::TypeLiteral::Char), _) => match b {
+                        ops::Value::Byte(x) => ctx.push(ops::Value::Char(x as char)),
+                        ops::Value::Int(x) => {
+                            let Some(c) = u32::try_from(x).ok().and_then(char::from_u32) else {
+                                report_err!(E_CAST, token.pos, "{} er ikke en gyldig bokstav", x);
+                            };
+                            ctx.push(ops::Value::Char(c))
+                        }
+                        _ => {
+                            report_err!(E_CAST, token.pos, "Kunne ikke omgjøre {} til {}", b, typ);
                         }
                     },
                     (ops::Value::TypeLiteral(ops::TypeLiteral::Ptr), _) => match b {
                         ops::Value::Str(x) => {
-                            let new_x = (x.0, x.1, ops::TypeLiteral::Char);
+                            let new_x = ops::Pointer::new(x.0, x.1, ops::TypeLiteral::Char, 1, x.2);
                             ctx.push(ops::Value::Ptr(new_x))
                         }
                         _ => {
-                            report_err!(token.pos, "Kunne ikke omgjøre {} til {}", b, typ);
+                            report_err!(E_CAST, token.pos, "Kunne ikke omgjøre {} til {}", b, typ);
                         }
                     },
                     (_, _) => {
-                        report_err!(token.pos,"Kunne ikke omgjøre {} til {}. Andre argument må være en bokstavelig type", b, typ);
+                        report_err!(E_CAST, token.pos,"Kunne ikke omgjøre {} til {}. Andre argument må være en bokstavelig type", b, typ);
                     }
                 }
             }
@@ -694,10 +1380,19 @@ pub fn execute(
                 let ptr = ctx.pop().unwrap();
 
                 if let ops::Value::Ptr(x) = ptr {
-                    let val = ctx.read(x.0).unwrap();
+                    if let ops::TypeLiteral::Struct(_) = x.typ {
+                        report_err!(E_MEM, token.pos, "Kan ikke lese en hel struktur, bruk et felt");
+                    }
+                    if !x.in_bounds(1) {
+                        report_err!(E_MEM, token.pos, "Kan ikke lese utenfor grensene til [{}; {}]", x.typ, x.len);
+                    }
+                    if !ctx.is_live(x.ptr, 1, x.alloc) {
+                        report_err!(E_MEM, token.pos, "Kan ikke lese fra frigjort minne");
+                    }
+                    let val = ctx.read(x.ptr).unwrap();
                     ctx.push(val)
                 } else {
-                    report_err!(token.pos, "Kunne ikke lese fra minne adresse '{}'", ptr);
+                    report_err!(E_MEM, token.pos, "Kunne ikke lese fra minne adresse '{}'", ptr);
                 }
             }
             ops::Operator::Write => {
@@ -707,28 +1402,54 @@ pub fn execute(
                 let val = ctx.pop().unwrap();
 
                 if let ops::Value::Ptr(x) = ptr {
-                    if !val.eq(&x.2) {
-                        report_err!(token.pos, "Forventet {:?} men fant {}", x.2, val);
+                    // en peker til en struktur er også lik strukturens type, så den
+                    // må stoppes før den havner der strukturens felt ligger
+                    if let ops::TypeLiteral::Struct(_) = x.typ {
+                        report_err!(E_MEM, token.pos, "Kan ikke skrive en hel struktur, bruk et felt");
+                    }
+                    if !val.eq(&x.typ) {
+                        report_err!(E_TYPE, token.pos, "Forventet {:?} men fant {}", x.typ, val);
+                    }
+                    if !x.in_bounds(1) {
+                        report_err!(E_MEM, token.pos, "Kan ikke skrive utenfor grensene til [{}; {}]", x.typ, x.len);
+                    }
+                    if !ctx.is_live(x.ptr, 1, x.alloc) || ctx.over_write(x.ptr, &val).is_none() {
+                        report_err!(E_MEM, token.pos, "Kan ikke skrive til frigjort minne");
                     }
-                    ctx.over_write(x.0, &val)
                 } else {
-                    return Err((
-                        "'!' operator krever at første operator er en peker",
-                        token.pos.clone(),
-                    ));
+                    report_err!(E_TYPE, token.pos, "'!' operator krever at første operator er en peker");
+                }
+            }
+            ops::Operator::Free => {
+                check_stack_min!(ctx, token, 1, "'frigjør' operator krever minst 1 argument");
+
+                let val = ctx.pop().unwrap();
+                let res = match val {
+                    ops::Value::Ptr(x) => ctx.free(x.ptr, x.alloc),
+                    ops::Value::Str((_, 0, _)) => Ok(()),
+                    ops::Value::Str((ptr, _, id)) => ctx.free(ptr, id),
+                    _ => {
+                        report_err!(E_TYPE, token.pos, "'frigjør {}' er ikke støttet", val);
+                    }
+                };
+                if let Err(e) = res {
+                    report_err!(E_MEM, token.pos, "{}", e);
                 }
             }
             ops::Operator::Word => {
                 if let Some(key) = &token.name {
                     if let Some((Some(ops::Value::FuncPtr(func_ptr)), _)) = ctx.def.clone().get(key) {
-                        let Some(res) = ctx.call(&func_ptr, i) else {
-                            report_err!(token.pos, "feil argumenter for funksjon '{}'", key);
+                        let Some(res) = ctx.call(func_ptr, i) else {
+                            report_err!(E_FUNC, token.pos, "feil argumenter for funksjon '{}'", key);
                         };
                         i = res
+                    } else if let Some((val, _, _)) = ctx.get_var(key) {
+                        let val = val.clone();
+                        ctx.push(val)
                     } else if let Some((Some(val), _)) = ctx.def.clone().get(key) {
                         ctx.push(val.clone())
                     } else {
-                        report_err!(token.pos, "Ukjent ord '{}'", key);
+                        report_err!(E_UNKNOWN, token.pos, "Ukjent ord '{}'", key);
                     }
                 }
             }
@@ -736,35 +1457,39 @@ pub fn execute(
                 check_stack_min!(ctx, token, 1, "'avslutt' operator krever minst 1 argument");
                 let code = ctx.pop().unwrap();
                 if let ops::Value::Int(x) = code {
+                    let Ok(x) = u8::try_from(x) else {
+                        report_err!(E_RANGE, token.pos, "Avslutnings koden må være fra 0 til 255, fant {}", x);
+                    };
                     let _ = stdout().flush();
-                    return Ok(x as u8);
+                    hook.after(ctx, prg, current);
+                    return Ok(x);
                 } else {
-                    return Err(("Avslutnings kode må være ett 'Helt'", token.pos.clone()));
+                    report_err!(E_TYPE, token.pos, "Avslutnings kode må være ett 'Helt'");
                 }
             }
             ops::Operator::Func => {
                 let name = &prg[i + 1];
                 if name.op == ops::Operator::Word {
                     let Some(key) = &name.name else {
-                        report_err!(token.pos, "Kunne ikke finne navn");
+                        report_err!(E_DEF, token.pos, "Kunne ikke finne navn");
                     };
                     let mut params: Vec<ops::TypeLiteral> = vec![];
                     let mut returns: Vec<ops::TypeLiteral> = vec![];
                     let mut all_params_found = false;
 
                     let mut j = i + 1;
-                    while &prg[j].op != &ops::Operator::In {
+                    while prg[j].op != ops::Operator::In {
                         let current_argument = &prg[j];
                         if !all_params_found {
                             match &current_argument.val {
                                 Some(ops::Value::TypeLiteral(arg_typ)) => {params.push(*arg_typ)},
-                                Some(_) => {report_err!(current_argument.pos, "Forventet 'TypeLitr' men fant {}", current_argument.val.as_ref().unwrap());},
+                                Some(_) => {report_err!(E_TYPE, current_argument.pos, "Forventet 'TypeLitr' men fant {}", current_argument.val.as_ref().unwrap());},
                                 None => ()
                             }
                         } else {
                             match &current_argument.val {
                                 Some(ops::Value::TypeLiteral(arg_typ)) => {returns.push(*arg_typ)},
-                                Some(_) => {report_err!(current_argument.pos, "Forventet 'TypeLitr' men fant {}", current_argument.val.as_ref().unwrap());},
+                                Some(_) => {report_err!(E_TYPE, current_argument.pos, "Forventet 'TypeLitr' men fant {}", current_argument.val.as_ref().unwrap());},
                                 None => ()
                             }
                         }
@@ -785,10 +1510,28 @@ pub fn execute(
                     ctx.def
                         .insert(key.to_string(), (Some(ops::Value::FuncPtr(func_ptr)), ctx.current_scope));
                 } else {
-                    report_err!(token.pos, "Kunne ikke finne navn til funksjon");
+                    report_err!(E_FUNC, token.pos, "Kunne ikke finne navn til funksjon");
                 }
                 i = token.arg.unwrap()
             }
+            ops::Operator::Struct => i = token.arg.unwrap(),
+            ops::Operator::Field => {
+                check_stack_min!(ctx, token, 1, "felt operator krever minst 1 argument");
+
+                let name = token.name.as_ref().unwrap();
+                let ptr = ctx.pop().unwrap();
+
+                let ops::Value::Ptr(x @ ops::Pointer { typ: ops::TypeLiteral::Struct(id), .. }) = ptr else {
+                    report_err!(E_TYPE, token.pos, "'.{}' krever en peker til en struktur, fant {}", name, ptr);
+                };
+                let Some((typ, offset)) = ctx.structs[id].field(name) else {
+                    report_err!(E_UNKNOWN, token.pos, "Strukturen '{}' har ikke feltet '{}'", ctx.structs[id].name, name);
+                };
+                if !x.in_bounds(ctx.structs[id].size) {
+                    report_err!(E_MEM, token.pos, "Pekeren er utenfor grensene til [{}; {}]", x.typ, x.len);
+                }
+                ctx.push(ops::Value::Ptr(ops::Pointer::new(x.ptr + offset, 1, typ, ctx.size_of(&typ), x.alloc)))
+            }
             ops::Operator::In => (),
             ops::Operator::BikeShed => (),
             ops::Operator::Let => {
@@ -804,40 +1547,94 @@ pub fn execute(
                     ctx.current_scope += 1;
                     for name in vars {
                         let Some(val) = ctx.pop() else {
-                            report_err!(token.pos, "Ikke nokk verdier på stabelen for let-binding");
+                            report_err!(E_STACK, token.pos, "Ikke nokk verdier på stabelen for let-binding");
                         };
                         ctx.def.insert(name.clone(), (Some(val), ctx.current_scope));
                     }
                     i = j
                 } else {
-                    report_err!(prg[j].pos, "forventet 'inni' men fant '{:?}'", prg[j].op);
+                    report_err!(E_DEF, prg[j].pos, "forventet 'inni' men fant '{:?}'", prg[j].op);
                 }
             }
             ops::Operator::Debug => {
                 let _ = stdout().flush();
-                println!("");
-                let width = termsize::get().unwrap().cols.into();
-
-                print!("Stabel: ");
-                let mut stack = String::from("");
-                for v in &ctx.stack {
-                    stack.push_str(&format!("{}, ", v.to_string(ctx)));
-                }
-
-                if (stack.len() + 8) <= width {
-                    print!("{}", stack);
-                } else {
-                    print!(
-                        "...{}",
-                        &stack[(stack.len() - (width - 11))..(stack.len() - 1)]
-                    );
-                }
-                print!("\n");
+                println!();
+                println!("{}", ctx.stack_line());
             }
         }
         // println!("{:?}", token.op);
+        hook.after(ctx, prg, current);
         i += 1;
     }
 
     Ok(0)
 }
+
+// Runder et flyttall mot null, eller None hvis det ikke får plass i `T`.
+fn float_to_int<T: TryFrom<i128>>(x: f64) -> Option<T> {
+    // sjekker grensen før `as`, som ellers ville stoppet ved i128 sine grenser
+    if !x.is_finite() || x.abs() >= 1e30 {
+        return None;
+    }
+    T::try_from(x.trunc() as i128).ok()
+}
+
+// Tar teksten i en 'Str' fra stabelen, eller en 'Bokst' som en tekst med én bokstav.
+fn pop_text(ctx: &mut Runtime, token: &ops::Instruction) -> Result<String, Diagnostic> {
+    let val = ctx.pop().unwrap();
+    match val {
+        ops::Value::Char(x) => Ok(x.to_string()),
+        ops::Value::Str(_) => match ctx.read_str(&val) {
+            Some(x) => Ok(x),
+            None => report_err!(E_MEM, token.pos, "Strengen peker til frigjort minne"),
+        },
+        _ => report_err!(E_TYPE, token.pos, "'{}' krever en 'Str', fant {}", op_name(token.op), val),
+    }
+}
+
+fn index(token: &ops::Instruction, val: &ops::Value) -> Result<usize, Diagnostic> {
+    match val {
+        ops::Value::Int(x) if *x >= 0 => Ok(*x as usize),
+        ops::Value::Int(x) => report_err!(E_RANGE, token.pos, "'{}' kan ikke bruke et negativt tall, fant {}", op_name(token.op), x),
+        _ => report_err!(E_TYPE, token.pos, "'{}' krever en 'Helt', fant {}", op_name(token.op), val),
+    }
+}
+
+// Filen `val` peker til, hvis den fortsatt er åpen.
+fn file<'a>(ctx: &'a mut Runtime, token: &ops::Instruction, val: &ops::Value) -> Result<&'a mut FileHandle, Diagnostic> {
+    let ops::Value::File(x) = val else {
+        report_err!(E_TYPE, token.pos, "'{}' krever en 'Fil', fant {}", op_name(token.op), val);
+    };
+    match ctx.files.get_mut(*x) {
+        Some(Some(handle)) => Ok(handle),
+        _ => report_err!(E_IO, token.pos, "Filen er lukket"),
+    }
+}
+
+// Leser én bokstav i UTF-8, eller None på slutten av `input`.
+fn read_char(input: &mut impl BufRead) -> std::io::Result<Option<char>> {
+    let mut buf = [0; 4];
+    if input.read(&mut buf[..1])? == 0 {
+        return Ok(None);
+    }
+    let len = match buf[0] {
+        x if x < 0x80 => 1,
+        x if x >> 5 == 0b110 => 2,
+        x if x >> 4 == 0b1110 => 3,
+        _ => 4,
+    };
+    input.read_exact(&mut buf[1..len])?;
+    match std::str::from_utf8(&buf[..len]) {
+        Ok(x) => Ok(x.chars().next()),
+        Err(e) => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+    }
+}
+
+fn trim_newline(x: &mut String) {
+    if x.ends_with('\n') {
+        x.pop();
+    }
+    if x.ends_with('\r') {
+        x.pop();
+    }
+}
@@ -0,0 +1,66 @@
+use crate::lex;
+use std::fs;
+
+const INDENT: &str = "    ";
+
+// Ord som står på samme nivå som ordet som åpnet blokken, når de starter en linje.
+const DEDENT: [&str; 4] = ["ellvis", "ellers", "inni", "slutt"];
+
+// Formaterer filene. Med `check` blir filene ikke endret, men avslutnings koden
+// blir 1 hvis noen av dem ikke er formatert.
+pub fn run(paths: &[String], check: bool) -> i32 {
+    let mut code = 0;
+    for path in paths {
+        let src = match fs::read_to_string(path) {
+            Ok(x) => x,
+            Err(e) => {
+                eprintln!("Kunne ikke lese '{}': {}", path, e);
+                code = 1;
+                continue;
+            }
+        };
+        let formatted = format(&src, path);
+        if formatted == src {
+            continue;
+        }
+        if check {
+            println!("{} er ikke formatert", path);
+            code = 1
+        } else if let Err(e) = fs::write(path, formatted) {
+            eprintln!("Kunne ikke skrive til '{}': {}", path, e);
+            code = 1
+        }
+    }
+    code
+}
+
+// Rykker inn hver linje med 4 mellomrom per blokk den står i, og fjerner
+// mellomrom på slutten av linjene. Resten av linjen blir ikke endret.
+pub fn format(src: &str, path: &str) -> String {
+    let mut out = String::new();
+    let mut depth: usize = 0;
+    for line in src.lines() {
+        let words: Vec<String> = lex::lex_source(std::iter::once(line), path)
+            .into_iter()
+            .map(|(word, _)| word)
+            .filter(|x| !x.is_empty())
+            .collect();
+
+        let line = line.trim();
+        if !line.is_empty() {
+            let dedent = words.first().is_some_and(|x| DEDENT.contains(&x.as_str()));
+            out.push_str(&INDENT.repeat(if dedent { depth.saturating_sub(1) } else { depth }));
+            out.push_str(line);
+        }
+        out.push('\n');
+
+        for word in words {
+            if lex::OPENERS.contains(&word.as_str()) {
+                depth += 1
+            } else if word == "slutt" {
+                depth = depth.saturating_sub(1)
+            }
+        }
+    }
+    out
+}
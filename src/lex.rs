@@ -1,50 +1,149 @@
+use crate::diagnostic::{Diagnostic, E_FILE, E_IMPORT};
 use crate::ops::Pos;
-use std::fs::File;
+use crate::report_err;
+use snailquote::unescape;
+use std::collections::HashSet;
+use std::fs::{self, File};
 use std::io::{self, BufRead};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+// Ord som åpner en blokk som må stenges med 'slutt'.
+pub const OPENERS: [&str; 8] = ["hvis", "når", "konst", "minne", "funk", "let", "var", "struktur"];
+
 enum Mode {
     Normal,
     String,
 }
 
-pub fn lex(path: &String) -> Result<Vec<(String, Pos)>, (&'static str, Pos)> {
+pub fn lex(path: &String) -> Result<Vec<(String, Pos)>, Diagnostic> {
+    let mut imported: HashSet<PathBuf> = HashSet::new();
+    let mut importing: Vec<(PathBuf, Option<Pos>)> = vec![];
+    lex_file(path, None, &mut imported, &mut importing)
+}
+
+// `imported` er filene som allerede er lest, så hver fil bare tas med én gang.
+// `importing` er kjeden av filer som leses nå, for å finne sirkulære 'importer'.
+fn lex_file(
+    path: &String,
+    from: Option<&Pos>,
+    imported: &mut HashSet<PathBuf>,
+    importing: &mut Vec<(PathBuf, Option<Pos>)>,
+) -> Result<Vec<(String, Pos)>, Diagnostic> {
+    let err_pos = from.cloned().unwrap_or((1, 1, path.clone()));
+    let Ok(full_path) = fs::canonicalize(path) else {
+        report_err!(E_FILE, err_pos, "Fant ikke filen '{}'", path);
+    };
+
+    if let Some(k) = importing.iter().position(|(p, _)| p == &full_path) {
+        let start = match importing.get(k + 1) {
+            Some((_, Some(pos))) => pos.clone(),
+            _ => err_pos.clone(),
+        };
+        return Err(
+            Diagnostic::error(E_IMPORT, &err_pos, format!("Sirkulær import av '{}'", path))
+                .with_secondary(&start, "sirkelen starter her"),
+        );
+    }
+    if imported.contains(&full_path) {
+        return Ok(vec![]);
+    }
+
+    importing.push((full_path.clone(), from.cloned()));
+    let tokens = lex_lines(path)?;
+    let prg = splice_imports(tokens, path, imported, importing)?;
+
+    importing.pop();
+    imported.insert(full_path);
+    Ok(prg)
+}
+
+// Leser kode som ikke kommer fra en fil, som en linje skrevet i REPL-en.
+// Stier i 'importer' er relative til mappen aepp kjøres fra.
+pub fn lex_str(src: &str, path: &str) -> Result<Vec<(String, Pos)>, Diagnostic> {
+    let tokens = lex_source(src.lines(), path);
+    splice_imports(tokens, &path.to_string(), &mut HashSet::new(), &mut vec![])
+}
+
+fn splice_imports(
+    tokens: Vec<(String, Pos)>,
+    path: &String,
+    imported: &mut HashSet<PathBuf>,
+    importing: &mut Vec<(PathBuf, Option<Pos>)>,
+) -> Result<Vec<(String, Pos)>, Diagnostic> {
     let mut prg: Vec<(String, Pos)> = vec![];
+    let mut i = 0;
+    while i < tokens.len() {
+        let (token, pos) = &tokens[i];
+        if token != "importer" {
+            prg.push(tokens[i].clone());
+            i += 1;
+            continue;
+        }
 
-    if let Ok(lines) = read_lines(path) {
-        for (i, line) in lines.enumerate() {
-            if let Ok(ip) = line {
-                let mut col = 1;
-                let mut word = String::from("");
-                let mut mode = Mode::Normal;
-                for char in ip.chars() {
-                    match (char, &mode) {
-                        ('#', Mode::Normal) => break,
-                        ('"', Mode::Normal) => {
-                            word.push(char);
-                            mode = Mode::String
-                        }
-                        ('"', Mode::String) => {
-                            word.push(char);
-                            mode = Mode::Normal
-                        }
-                        (' ', Mode::Normal) => {
-                            if !word.is_empty() {
-                                prg.push((word.clone(), (i + 1, col, path.clone())));
-                                col += word.len() + 1;
-                                word.clear();
-                            } else {
-                                col += 1;
-                            }
-                        }
-                        (_, _) => word.push(char),
+        i += 1;
+        while i < tokens.len() && tokens[i].0.is_empty() {
+            i += 1
+        }
+        let Some((file, _)) = tokens.get(i) else {
+            report_err!(E_IMPORT, pos, "'importer' forventer en sti");
+        };
+        if !file.starts_with('"') {
+            report_err!(E_IMPORT, pos, "'importer' forventer en sti i anførselstegn, fant '{}'", file);
+        }
+        let Ok(rel_path) = unescape(file) else {
+            report_err!(E_IMPORT, pos, "Kunne ikke lese sti {}", file);
+        };
+
+        let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+        let import_path = dir.join(rel_path).to_string_lossy().to_string();
+        let mut imported_prg = lex_file(&import_path, Some(pos), imported, importing)?;
+        prg.append(&mut imported_prg);
+        i += 1;
+    }
+    Ok(prg)
+}
+
+fn lex_lines(path: &String) -> Result<Vec<(String, Pos)>, Diagnostic> {
+    let Ok(lines) = read_lines(path) else {
+        return Ok(vec![]);
+    };
+    Ok(lex_source(lines.map_while(Result::ok), path))
+}
+
+pub fn lex_source<S: AsRef<str>>(lines: impl Iterator<Item = S>, path: &str) -> Vec<(String, Pos)> {
+    let mut prg: Vec<(String, Pos)> = vec![];
+
+    for (i, ip) in lines.enumerate() {
+        let mut col = 1;
+        let mut word = String::from("");
+        let mut mode = Mode::Normal;
+        for char in ip.as_ref().chars() {
+            match (char, &mode) {
+                ('#', Mode::Normal) => break,
+                ('"', Mode::Normal) => {
+                    word.push(char);
+                    mode = Mode::String
+                }
+                ('"', Mode::String) => {
+                    word.push(char);
+                    mode = Mode::Normal
+                }
+                (' ', Mode::Normal) => {
+                    if !word.is_empty() {
+                        prg.push((word.clone(), (i + 1, col, path.to_string())));
+                        col += word.len() + 1;
+                        word.clear();
+                    } else {
+                        col += 1;
                     }
                 }
-                prg.push((word.clone(), (i + 1, col, path.clone())));
+                (_, _) => word.push(char),
             }
         }
+        prg.push((word.clone(), (i + 1, col, path.to_string())));
     }
 
-    Ok(prg)
+    prg
 }
 
 fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
@@ -1,12 +1,21 @@
-use std::collections::HashMap;
-use std::{env, fmt};
-use termsize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, Write};
+use std::{env, fmt, fs, process};
 
+mod check;
+mod cli;
 mod cross_ref;
+mod debug;
+mod diagnostic;
 mod execute;
+mod format;
 mod lex;
 mod ops;
 mod parse;
+mod profile;
+mod repl;
+mod tester;
+mod trace;
 
 #[derive(Debug)]
 pub struct Runtime {
@@ -14,9 +23,40 @@ pub struct Runtime {
     mem: Vec<ops::Value>,
     pub current_scope: usize,
     top: usize,
+    allocs: BTreeMap<ops::Ptr, Alloc>,    // start -> minne som er i bruk
+    next_alloc: usize,
+    free_list: Vec<(ops::Ptr, usize)>, // frigjort minne, sortert etter start
     pub def: HashMap<String, (Option<ops::Value>, usize)>,
     pub return_stack: Vec<usize>,
     frame_stack: Vec<Vec<ops::Value>>,
+    local_mem: Vec<Vec<(ops::Ptr, usize)>>, // minne definert i hvert funksjons kall
+    written: Vec<HashSet<ops::Ptr>>,        // celler skrevet til med '->' i hvert funksjons kall
+    pub vars: Vec<HashMap<String, (ops::Value, ops::TypeLiteral, usize)>>,
+    pub structs: Vec<ops::StructDef>,
+    pub warnings: Vec<diagnostic::Diagnostic>,
+    pub args: Vec<String>, // argumentene til programmet, skrevet etter '--'
+    pub files: Vec<Option<FileHandle>>, // None når filen er lukket
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Alloc {
+    len: usize,
+    // hvert minne får en ny id, så en gammel peker ikke kan brukes når minnet blir gjenbrukt
+    id: usize,
+    literal: bool, // strenger skrevet i koden, som ikke kan frigjøres
+}
+
+// En fil åpnet med 'åpne'. `ops::Value::File` er indeksen i `Runtime::files`.
+#[derive(Debug)]
+pub enum FileHandle {
+    Read(io::BufReader<fs::File>),
+    Write(io::BufWriter<fs::File>),
+}
+
+impl Default for Runtime {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Runtime {
@@ -25,10 +65,20 @@ impl Runtime {
             stack: vec![],
             mem: vec![],
             top: 0,
+            allocs: BTreeMap::new(),
+            next_alloc: 0,
+            free_list: vec![],
             def: HashMap::new(),
             return_stack: vec![],
             frame_stack: vec![],
+            local_mem: vec![],
+            written: vec![],
+            vars: vec![HashMap::new()],
             current_scope: 0,
+            structs: vec![],
+            warnings: vec![],
+            args: vec![],
+            files: vec![],
         }
     }
 
@@ -49,12 +99,12 @@ impl Runtime {
 
     pub fn call(&mut self, func: &ops::FuncPtr, i: usize) -> Option<usize> {
         let new_stack: Vec<ops::Value> = {
-            let start = self.stack.len() - func.params.len();
+            let start = self.stack.len().checked_sub(func.params.len())?;
             let params: Vec<Option<ops::Value>> = self.stack.drain(start..)
                 .enumerate()
                 .map(|(j, par)| {
                     if !par.eq(&func.params[j]) {
-                        return None;
+                        None
                     } else {Some(par)}
                 })
                 .collect();
@@ -69,41 +119,243 @@ impl Runtime {
             arg
         };
         self.frame_stack.push(self.stack.clone());
+        self.vars.push(HashMap::new());
+        self.local_mem.push(vec![]);
+        self.written.push(HashSet::new());
         self.stack = new_stack;
         self.return_stack.push(i);
         self.current_scope += 1;
         Some(func.ptr)
     }
 
-    pub fn retur(&mut self, func: &ops::FuncPtr) -> Option<usize> {
+    pub fn retur(&mut self, func: &ops::FuncPtr) -> Result<usize, &'static str> {
         let mut returned_items: Vec<ops::Value> = {
-            let start = self.stack.len() - func.returns.len();
+            let Some(start) = self.stack.len().checked_sub(func.returns.len()) else {
+                return Err("ikke nok retur verdier for");
+            };
             let returns = self.stack.get(start..).unwrap();
             for (j, par) in returns.iter().enumerate() {
                 if !par.eq(&func.returns[j]) {
-                    return None;
+                    return Err("ikke rette retur verdier for");
                 }
             }
             returns.into()
         };
 
+        // en peker til lokalt minne kan bare slippe ut gjennom retur verdiene, variabler
+        // utenfor funksjonen, eller celler utenfor det lokale minnet som er skrevet til
+        let local_mem = self.local_mem.pop().unwrap_or_default();
+        let written = self.written.pop().unwrap_or_default();
+        let ids: Vec<usize> = local_mem.iter().filter_map(|(start, _)| self.alloc_id(*start)).collect();
+        let escapes = |val: &ops::Value| !ids.is_empty() && points_into(val, &ids);
+        let outside = |j: &&ops::Ptr| self.alloc_id(**j).is_none_or(|id| !ids.contains(&id));
+        if returned_items.iter().any(escapes)
+            || written.iter().filter(outside).any(|j| self.mem.get(*j).is_some_and(escapes))
+            || self.vars[..self.vars.len() - 1].iter().flat_map(|x| x.values()).any(|(val, _, _)| escapes(val))
+        {
+            self.local_mem.push(local_mem);
+            self.written.push(written);
+            return Err("peker til lokalt minne slipper ut av");
+        }
+        for (start, _) in local_mem {
+            self.release(start);
+        }
+        if let Some(outer) = self.written.last_mut() {
+            outer.extend(written)
+        }
+
         let old_stack = self.frame_stack.pop().unwrap();
+        self.vars.pop();
         self.stack = old_stack;
         self.stack.append(&mut returned_items);
-        self.return_stack.pop()
+        Ok(self.return_stack.pop().unwrap())
+    }
+
+    // hvert funksjons kall som er i gang, innerst først, med navnet og hvor det ble kalt fra
+    pub fn call_trace(&self, prg: &[ops::Instruction]) -> Vec<(String, ops::Pos)> {
+        self.return_stack
+            .iter()
+            .rev()
+            .filter_map(|i| prg.get(*i))
+            .map(|x| (x.name.clone().unwrap_or_default(), x.pos.clone()))
+            .collect()
+    }
+
+    // rydder opp etter en feil midt i en funksjon eller blokk, så REPL-en kan fortsette på toppnivå
+    pub fn unwind(&mut self) {
+        if !self.frame_stack.is_empty() {
+            self.stack = self.frame_stack.swap_remove(0);
+            self.frame_stack.clear();
+        }
+        let local_mem: Vec<(ops::Ptr, usize)> = self.local_mem.drain(..).flatten().collect();
+        for (start, _) in local_mem {
+            self.release(start);
+        }
+        self.written.clear();
+        self.return_stack.clear();
+        self.vars.truncate(1);
+        self.vars[0].retain(|_, (_, _, scope)| *scope == 0);
+        self.def.retain(|_, (_, scope)| *scope == 0);
+        self.current_scope = 0;
+    }
+
+    // registrerer minne som skal frigjøres når funksjonen returnerer
+    pub fn add_local(&mut self, ptr: ops::Ptr, len: usize) {
+        if let Some(frame) = self.local_mem.last_mut() {
+            frame.push((ptr, len))
+        }
+    }
+
+    // variabelen `name` i dette kallet eller den globale, hvis ikke en 'let', 'konst'
+    // eller 'minne' med samme navn er definert like dypt eller dypere
+    pub fn get_var(&mut self, name: &str) -> Option<&mut (ops::Value, ops::TypeLiteral, usize)> {
+        let frame = self.vars.len() - 1;
+        let frame = if self.vars[frame].contains_key(name) { frame } else { 0 };
+        let (_, _, scope) = self.vars[frame].get(name)?;
+        if self.def.get(name).is_some_and(|(_, def_scope)| def_scope >= scope) {
+            return None;
+        }
+        self.vars[frame].get_mut(name)
+    }
+
+    pub fn size_of(&self, typ: &ops::TypeLiteral) -> usize {
+        match typ {
+            ops::TypeLiteral::Struct(id) => self.structs[*id].size,
+            _ => 1,
+        }
+    }
+
+    // gir starten, lengden og id-en til minnet
+    pub fn write(&mut self, data: &Vec<ops::Value>) -> (ops::Ptr, usize, usize) {
+        let len = data.len();
+        let free = self.free_list.iter().position(|(_, free_len)| *free_len >= len);
+        let ptr = match free {
+            Some(j) if len > 0 => {
+                let (ptr, free_len) = self.free_list[j];
+                if free_len == len {
+                    self.free_list.remove(j);
+                } else {
+                    self.free_list[j] = (ptr + len, free_len - len);
+                }
+                for (k, val) in data.iter().enumerate() {
+                    self.mem[ptr + k] = val.clone();
+                }
+                ptr
+            }
+            _ => {
+                let ptr = self.top;
+                for val in data {
+                    self.mem.push(val.clone());
+                    self.top += 1;
+                }
+                ptr
+            }
+        };
+        let id = self.next_alloc;
+        self.next_alloc += 1;
+        if len > 0 {
+            self.allocs.insert(ptr, Alloc { len, id, literal: false });
+        }
+        (ptr, len, id)
+    }
+
+    // en streng fra koden, som lever like lenge som programmet
+    pub fn write_literal(&mut self, data: &Vec<ops::Value>) -> (ops::Ptr, usize, usize) {
+        let res = self.write(data);
+        if let Some(alloc) = self.allocs.get_mut(&res.0) {
+            alloc.literal = true
+        }
+        res
+    }
+
+    // `id` er id-en til pekeren, og må være lik id-en til minnet den peker til
+    pub fn free(&mut self, ptr: ops::Ptr, id: usize) -> Result<(), &'static str> {
+        match self.allocs.get(&ptr) {
+            Some(alloc) if alloc.id != id => Err("Minnet er allerede frigjort"),
+            Some(alloc) if alloc.literal => Err("Kan ikke frigjøre en streng skrevet i koden"),
+            Some(_) => {
+                self.release(ptr);
+                Ok(())
+            }
+            None if self.allocs.values().any(|x| x.id == id) => Err("Kan bare frigjøre starten av et minne"),
+            None => Err("Minnet er allerede frigjort"),
+        }
+    }
+
+    fn release(&mut self, ptr: ops::Ptr) {
+        let Some(Alloc { len, .. }) = self.allocs.remove(&ptr) else {
+            return;
+        };
+        for frame in &mut self.local_mem {
+            frame.retain(|(start, _)| *start != ptr);
+        }
+        for cell in &mut self.mem[ptr..ptr + len] {
+            *cell = ops::Value::Null;
+        }
+
+        let j = self.free_list.partition_point(|(start, _)| *start < ptr);
+        self.free_list.insert(j, (ptr, len));
+        if j + 1 < self.free_list.len() && ptr + len == self.free_list[j + 1].0 {
+            self.free_list[j].1 += self.free_list.remove(j + 1).1;
+        }
+        if j > 0 && self.free_list[j - 1].0 + self.free_list[j - 1].1 == ptr {
+            self.free_list[j - 1].1 += self.free_list.remove(j).1;
+        }
+
+        if let Some(&(start, len)) = self.free_list.last() {
+            if start + len == self.top {
+                self.free_list.pop();
+                self.mem.truncate(start);
+                self.top = start;
+            }
+        }
+    }
+
+    // id-en til minnet som `ptr` peker inn i, hvis det ikke er frigjort
+    pub fn alloc_id(&self, ptr: ops::Ptr) -> Option<usize> {
+        let (start, alloc) = self.allocs.range(..=ptr).next_back()?;
+        (ptr < start + alloc.len).then_some(alloc.id)
+    }
+
+    pub fn is_live(&self, ptr: ops::Ptr, len: usize, id: usize) -> bool {
+        len == 0
+            || self
+                .allocs
+                .range(..=ptr)
+                .next_back()
+                .is_some_and(|(start, alloc)| alloc.id == id && ptr + len <= start + alloc.len)
     }
 
-    pub fn write(&mut self, data: &Vec<ops::Value>) -> (ops::Ptr, usize) {
-        let ptr = self.top;
-        for val in data {
-            self.mem.push(val.clone());
-            self.top += 1;
+    pub fn over_write(&mut self, ptr: ops::Ptr, data: &ops::Value) -> Option<()> {
+        *self.mem.get_mut(ptr)? = data.clone();
+        if let Some(written) = self.written.last_mut() {
+            written.insert(ptr);
         }
-        (ptr, data.len())
+        Some(())
+    }
+
+    // skriver teksten til minnet som den er, uten å tolke escape tegn
+    pub fn write_str(&mut self, x: &str) -> ops::Value {
+        let data: Vec<ops::Value> = x.chars().map(ops::Value::Char).collect();
+        ops::Value::Str(self.write(&data))
     }
 
-    pub fn over_write(&mut self, ptr: ops::Ptr, data: &ops::Value) {
-        self.mem[ptr] = data.clone()
+    // `mode` er "les", "skriv" eller "legg til"
+    pub fn open(&mut self, path: &str, mode: &str) -> io::Result<ops::Value> {
+        let handle = match mode {
+            "les" => FileHandle::Read(io::BufReader::new(fs::File::open(path)?)),
+            "skriv" => FileHandle::Write(io::BufWriter::new(fs::File::create(path)?)),
+            "legg til" => {
+                let file = fs::OpenOptions::new().append(true).create(true).open(path)?;
+                FileHandle::Write(io::BufWriter::new(file))
+            }
+            _ => {
+                let msg = format!("Ukjent modus '{}', forventet \"les\", \"skriv\" eller \"legg til\"", mode);
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+            }
+        };
+        self.files.push(Some(handle));
+        Ok(ops::Value::File(self.files.len() - 1))
     }
 
     pub fn read(&self, ptr: ops::Ptr) -> Option<ops::Value> {
@@ -115,14 +367,16 @@ impl Runtime {
     }
 
     pub fn read_str(&self, str_ptr: &ops::Value) -> Option<String> {
-        if let ops::Value::Str((ptr, len)) = str_ptr {
+        if let ops::Value::Str((ptr, len, id)) = str_ptr {
+            if !self.is_live(*ptr, *len, *id) {
+                return None;
+            }
             Some(
-                self.read_data(*ptr, *len)
-                    .unwrap()
+                self.read_data(*ptr, *len)?
                     .iter()
                     .map(|x| {
                         if let ops::Value::Char(c) = x {
-                            c.clone()
+                            *c
                         } else {
                             '\0'
                         }
@@ -135,108 +389,212 @@ impl Runtime {
     }
 }
 
-impl fmt::Display for Runtime {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let width = termsize::get().unwrap().cols.into();
+// om verdien peker til et av minnene med id-ene i `ids`
+fn points_into(val: &ops::Value, ids: &[usize]) -> bool {
+    match val {
+        ops::Value::Ptr(x) => ids.contains(&x.alloc),
+        ops::Value::Str((_, len, id)) => *len > 0 && ids.contains(id),
+        _ => false,
+    }
+}
 
-        write!(f, "Stabel: ")?;
+impl Runtime {
+    pub fn stack_line(&self) -> String {
         let mut stack = String::from("");
         for v in &self.stack {
             stack.push_str(&format!("{}, ", v.to_string(self)));
         }
 
-        if (stack.len() + 8) <= width {
-            write!(f, "{}\n", stack)?;
+        let width = term_width();
+        let len = stack.chars().count();
+        if (len + 8) <= width {
+            format!("Stabel: {}", stack)
         } else {
-            write!(
-                f,
-                "...{}\n",
-                &stack[(stack.len() - (width - 11))..(stack.len() - 1)]
-            )?;
+            let rest: String = stack.chars().skip(len - width.saturating_sub(11).min(len)).collect();
+            format!("Stabel: ...{}", rest.trim_end_matches(' '))
         }
+    }
 
-        write!(f, "Minne: ")?;
+    pub fn mem_line(&self) -> String {
         let mut mem = String::from("");
         for v in &self.mem {
             mem.push_str(&format!("{}, ", v.to_string(self)))
         }
-        if (mem.len() + 7) <= width {
-            write!(f, "{}\n", mem)?;
+
+        let width = term_width();
+        if (mem.chars().count() + 7) <= width {
+            format!("Minne: {}", mem)
         } else {
-            write!(f, "{}...\n", &mem[0..(width - 10)])?;
+            let start: String = mem.chars().take(width.saturating_sub(10)).collect();
+            format!("Minne: {}...", start)
         }
+    }
+}
 
-        Ok(())
+impl fmt::Display for Runtime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.stack_line())?;
+        writeln!(f, "{}", self.mem_line())
     }
 }
 
+// faller tilbake på 80 tegn når utdata ikke går til en terminal
+pub fn term_width() -> usize {
+    termsize::get().map_or(80, |x| x.cols.into())
+}
+
+// Avslutnings koder for feil, fra sysexits.h. Ellers avslutter programmet med
+// koden gitt til 'avslutt', eller 0.
+const EXIT_USAGE: i32 = 64; // feil i argumentene til aepp
+const EXIT_COMPILE: i32 = 65; // feil før programmet kjører
+const EXIT_RUNTIME: i32 = 70; // feil mens programmet kjører
+
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let flag: Option<&String>;
-    let path: &String;
-    if args.len() <= 2 {
-        path = &args[1];
-        flag = None;
-    } else {
-        path = &args[2];
-        flag = Some(&args[1])
-    }
+    let args: Vec<String> = env::args().skip(1).collect();
+    let cmd = match cli::parse(&args) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("Skriv 'aepp --hjelp' for hjelp");
+            process::exit(EXIT_USAGE);
+        }
+    };
 
-    match flag {
-        Some(x) => {
-            if x == "-d" {
-                let mut ctx = Runtime::new();
-                let res = debug_run(path, &mut ctx);
-                if let Err((e, pos)) = res {
-                    println!("{}:{}:{}  ERROR: {}\n", pos.2, pos.0, pos.1, e)
+    let code = match cmd {
+        cli::Command::Help => {
+            println!("{}", cli::USAGE);
+            0
+        }
+        cli::Command::Version => {
+            println!("aepp {}", env!("CARGO_PKG_VERSION"));
+            0
+        }
+        cli::Command::Repl(args) => {
+            repl::run(args);
+            0
+        }
+        cli::Command::Run(opts) => run_file(opts),
+        cli::Command::Check(paths) => check_files(&paths),
+        cli::Command::Fmt { paths, check } => format::run(&paths, check),
+        cli::Command::Test(dir) => tester::run(&dir),
+        cli::Command::Disasm(path) => disasm(&path),
+    };
+    let _ = io::stdout().flush();
+    process::exit(code)
+}
+
+fn run_file(opts: cli::RunOptions) -> i32 {
+    let mut hooks: Vec<Box<dyn execute::Hook>> = vec![];
+    if opts.debug {
+        hooks.push(Box::new(debug::Debugger::new()))
+    }
+    if opts.profile {
+        hooks.push(Box::new(profile::Profiler::new(opts.folded)))
+    }
+    if let Some(file) = opts.trace {
+        let out: Box<dyn Write> = if file.is_empty() {
+            Box::new(io::stderr())
+        } else {
+            match fs::File::create(&file) {
+                Ok(x) => Box::new(io::BufWriter::new(x)),
+                Err(e) => {
+                    eprintln!("Kunne ikke lage filen '{}': {}", file, e);
+                    return EXIT_USAGE;
                 }
-                println!("{}", ctx)
             }
-        }
-        None => {
-            let res = run(path);
-            if let Err((e, pos)) = res {
-                println!("{}:{}:{}  ERROR: {}\n", pos.2, pos.0, pos.1, e)
+        };
+        hooks.push(Box::new(trace::Tracer::new(out, opts.filter)))
+    }
+
+    let mut ctx = Runtime::new();
+    ctx.args = opts.args;
+    let code = run(&opts.path, &mut ctx, &mut hooks);
+    if opts.debug {
+        println!("{}", ctx)
+    }
+    code
+}
+
+// Returnerer avslutnings koden til programmet.
+fn run(path: &String, ctx: &mut Runtime, hooks: &mut Vec<Box<dyn execute::Hook>>) -> i32 {
+    let cross_refed = match compile(path, ctx) {
+        Ok(x) => x,
+        Err(errors) => {
+            for e in errors {
+                eprintln!("{}", e)
             }
+            return EXIT_COMPILE;
+        }
+    };
+    let res = if hooks.is_empty() {
+        execute::execute(ctx, &cross_refed)
+    } else {
+        execute::execute_with(ctx, &cross_refed, 0, hooks)
+    };
+    match res {
+        Ok(code) => code as i32,
+        Err(e) => {
+            eprintln!("{}", e);
+            EXIT_RUNTIME
         }
     }
 }
 
-fn debug_run(path: &String, ctx: &mut Runtime) -> Result<u8, (&'static str, ops::Pos)> {
-    let lexed = lex::lex(path)?;
-    let mut parsed = parse::parse(lexed, ctx)?;
-    let cross_refed = cross_ref::cross_reference(&mut parsed, &ctx)?;
-    // for (i, inst) in cross_refed.iter().enumerate() {
-    //     println!("{}: {}", i, inst)
-    // }
-    execute::execute(ctx, &cross_refed)
+fn check_files(paths: &[String]) -> i32 {
+    let mut code = 0;
+    for path in paths {
+        if let Err(errors) = compile(path, &mut Runtime::new()) {
+            for e in errors {
+                eprintln!("{}", e)
+            }
+            code = EXIT_COMPILE
+        }
+    }
+    code
 }
 
-fn run(path: &String) -> Result<u8, (&'static str, ops::Pos)> {
+// Skriver ut instruksjonene etter cross_reference, med hvor hopp og kall går.
+fn disasm(path: &String) -> i32 {
     let mut ctx = Runtime::new();
+    let prg = match compile(path, &mut ctx) {
+        Ok(x) => x,
+        Err(errors) => {
+            for e in errors {
+                eprintln!("{}", e)
+            }
+            return EXIT_COMPILE;
+        }
+    };
+    let width = prg.len().to_string().len();
+    let mut out = io::stdout().lock();
+    for (i, inst) in prg.iter().enumerate() {
+        let arg = inst.arg.map_or(String::new(), |x| format!("-> {}", x));
+        let pos = format!("{}:{}:{}", inst.pos.2, inst.pos.0, inst.pos.1);
+        let op = format!("{:?}", inst.op);
+        let text = trace::token_text(&ctx, inst);
+        // stopper stille når utdataen er lukket, som ved 'aepp disasm fil.ae | head'
+        if writeln!(out, "{:>w$}  {:<10} {:<8} {:<20} {}", i, op, arg, text, pos, w = width).is_err() {
+            return 0;
+        }
+    }
+    0
+}
 
-    let lexed = lex::lex(path)?;
-    let mut parsed = parse::parse(lexed, &mut ctx)?;
-    let cross_refed = cross_ref::cross_reference(&mut parsed, &ctx)?;
-    execute::execute(&mut ctx, &cross_refed)
+fn compile(path: &String, ctx: &mut Runtime) -> Result<Vec<ops::Instruction>, Vec<diagnostic::Diagnostic>> {
+    let lexed = lex::lex(path).map_err(|e| vec![e])?;
+    let mut parsed = parse::parse(lexed, ctx).map_err(|e| vec![e])?;
+    let cross_refed = cross_ref::cross_reference(&mut parsed, ctx);
+    for warning in ctx.warnings.drain(..) {
+        eprintln!("{}", warning)
+    }
+    let cross_refed = cross_refed?;
+    check::check(&cross_refed, ctx)?;
+    Ok(cross_refed)
 }
 
 #[macro_export]
 macro_rules! report_err {
-    ($tok:expr, $err_msg:expr) => {
-        return Err((
-            $err_msg,
-            $tok.clone(),
-        ))
+    ($code:expr, $pos:expr, $($arg:tt)*) => {
+        return Err($crate::diagnostic::Diagnostic::error($code, &$pos, format!($($arg)*)))
     };
-
-    ($pos:expr, $($arg:tt)*) => {
-        let err_s: String = format!($($arg)*).to_owned();
-        return Err((Box::leak(err_s.into_boxed_str()), $pos.clone()));
-    };
-
-    // ($pos:expr, $($arg:tt)*) => {
-    //     let err_s: String = format!($($arg)*).to_owned();
-    //     return Err((Box::leak(err_s.into_boxed_str()), $pos.clone()));
-    // };
 }
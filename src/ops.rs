@@ -41,11 +41,14 @@ impl fmt::Display for Instruction {
 pub enum Value {
     Int(i32),
     Float(f32),
+    Int64(i64),
+    Float64(f64),
     Bool(bool),
-    Str((Ptr, usize)), // Pointer to data in memory, size of string
-    Byte(u8),          //ikke implementert
+    Str((Ptr, usize, usize)), // peker til bokstavene i minnet, lengden og id-en til minnet
+    Byte(u8),
     Char(char),
-    Ptr((Ptr, usize, TypeLiteral)), // ikke implementert
+    Ptr(Pointer),
+    File(usize), // indeks i Runtime::files
 
     TypeLiteral(TypeLiteral),
     FuncPtr(FuncPtr),
@@ -57,11 +60,20 @@ impl Value {
         match self {
             Value::Int(_) => typ == &TypeLiteral::Int,
             Value::Float(_) => typ == &TypeLiteral::Float,
+            Value::Int64(_) => typ == &TypeLiteral::Int64,
+            Value::Float64(_) => typ == &TypeLiteral::Float64,
             Value::Bool(_) => typ == &TypeLiteral::Bool,
             Value::Str(_) => typ == &TypeLiteral::Str,
             Value::Byte(_) => typ == &TypeLiteral::Byte,
             Value::Char(_) => typ == &TypeLiteral::Char,
+            Value::Ptr(Pointer {
+                typ: TypeLiteral::Struct(id),
+                ..
+            }) => {
+                typ == &TypeLiteral::Ptr || typ == &TypeLiteral::Struct(*id)
+            }
             Value::Ptr(_) => typ == &TypeLiteral::Ptr,
+            Value::File(_) => typ == &TypeLiteral::File,
             _ => false,
         }
     }
@@ -70,6 +82,8 @@ impl Value {
         match self {
             Value::Int(x) => x.to_string(),
             Value::Float(x) => x.to_string(),
+            Value::Int64(x) => x.to_string(),
+            Value::Float64(x) => x.to_string(),
             Value::Bool(x) => {
                 if *x {
                     String::from("sann")
@@ -77,11 +91,14 @@ impl Value {
                     String::from("usann")
                 }
             }
-            Value::Str(_) => ctx.read_str(self).unwrap(),
-            Value::Byte(x) => String::from(format!("{:#02x}", x)),
+            Value::Str(_) => ctx
+                .read_str(self)
+                .unwrap_or_else(|| String::from("<frigjort>")),
+            Value::Byte(x) => format!("{:#04x}", x),
             Value::Char(x) => x.to_string(),
-            Value::Ptr((ptr, len, typ)) => String::from(format!("[{}; {}] -> {}", typ, len, ptr)),
-            Value::TypeLiteral(x) => String::from(format!("{}", x)),
+            Value::Ptr(x) => format!("[{}; {}] -> {}", x.typ, x.len, x.ptr),
+            Value::File(x) => format!("<fil {}>", x),
+            Value::TypeLiteral(x) => format!("{}", x),
             Value::Null => String::from("null"),
             _ => String::from("Kan ikke skrives"),
         }
@@ -93,12 +110,15 @@ impl fmt::Display for Value {
         match self {
             Value::Int(_) => write!(f, "Helt"),
             Value::Float(_) => write!(f, "Flyt"),
+            Value::Int64(_) => write!(f, "Helt64"),
+            Value::Float64(_) => write!(f, "Flyt64"),
             Value::Bool(_) => write!(f, "Bool"),
             Value::Str(_) => write!(f, "Str"),
             Value::TypeLiteral(_) => write!(f, "TypeLitr"),
             Value::Ptr(_) => write!(f, "Peker"),
             Value::Byte(_) => write!(f, "Byte"),
             Value::Char(_) => write!(f, "Bokst"),
+            Value::File(_) => write!(f, "Fil"),
             Value::Null => write!(f, "Null"),
             _ => write!(f, ""),
         }
@@ -109,11 +129,15 @@ impl fmt::Display for Value {
 pub enum TypeLiteral {
     Int,
     Float,
+    Int64,
+    Float64,
     Bool,
     Str,
     Byte,
     Char,
     Ptr,
+    File,
+    Struct(usize), // indeks i Runtime::structs
 }
 
 impl fmt::Display for TypeLiteral {
@@ -121,11 +145,15 @@ impl fmt::Display for TypeLiteral {
         match self {
             TypeLiteral::Int => write!(f, "Helt"),
             TypeLiteral::Float => write!(f, "Flyt"),
+            TypeLiteral::Int64 => write!(f, "Helt64"),
+            TypeLiteral::Float64 => write!(f, "Flyt64"),
             TypeLiteral::Bool => write!(f, "Bool"),
             TypeLiteral::Str => write!(f, "Str"),
             TypeLiteral::Ptr => write!(f, "Peker"),
             TypeLiteral::Byte => write!(f, "Byte"),
             TypeLiteral::Char => write!(f, "Bokst"),
+            TypeLiteral::File => write!(f, "Fil"),
+            TypeLiteral::Struct(id) => write!(f, "Struktur({})", id),
         }
     }
 }
@@ -140,6 +168,10 @@ pub enum Operator {
     Mem,
     Func,
     Let,
+    Var,
+    SetVar,
+    Struct,
+    Field,
 
     Word,
     BikeShed,
@@ -148,21 +180,59 @@ pub enum Operator {
     Sub,
     Mult,
     Div,
+    AddWrap,
+    SubWrap,
+    MultWrap,
+    AddSat,
+    SubSat,
+    MultSat,
 
     Cast,
 
     Print,
     PrintLn,
     Input,
+    InputLine,
+    InputAll,
+    InputChar,
     Debug,
+    Args,
+    Arg,
+    Env,
+
+    Len,
+    CharAt,
+    Substr,
+    Find,
+    Replace,
+    Split,
+    Trim,
+    Upper,
+    Lower,
+    StartsWith,
+    EndsWith,
+    IsDigit,
+    IsLetter,
+    IsSpace,
+
+    Open,
+    ReadLine,
+    ReadAll,
+    WriteFile,
+    Close,
 
     Read,
     Write,
+    Free,
     Exit,
 
     Not,
     And,
     Or,
+    Xor,
+    Shl,
+    Shr,
+    PopCount,
 
     Eq,
     Lt,
@@ -193,42 +263,81 @@ pub struct FuncPtr {
     pub returns: Vec<TypeLiteral>,
 }
 
-// skal gjøre pekere bedre senere.
-// #[warn(dead_code)]
-// pub struct Pointer {
-//     pub ptr: usize,
-//     pub len: usize,
-//     pub typ: TypeLiteral,
-//     max_ptr: usize,
-//     min_ptr: usize
-// }
-// #[warn(dead_code)]
-// impl Pointer {
-//     pub fn new(ptr: usize, len: usize, typ: TypeLiteral,) -> Self {
-//         Pointer {
-//             ptr,
-//             len,
-//             typ,
-//             min_ptr: ptr,
-//             max_ptr: ptr + len
-//         }
-//     }
-
-//     pub fn inc(&mut self, offset:usize) -> Option<&mut Self> {
-//         let new_ptr = self.ptr + offset;
-//         if new_ptr > self.max_ptr {
-//             return None;
-//         }
-//         self.ptr = new_ptr;
-//         Some(self)
-//     }
-
-//     pub fn dec(&mut self, offset:usize) -> Option<&mut Self> {
-//         let new_ptr = self.ptr - offset;
-//         if new_ptr < self.min_ptr {
-//             return None;
-//         }
-//         self.ptr = new_ptr;
-//         Some(self)
-//     }
-// }
+#[derive(Debug, PartialEq, Clone)]
+pub struct StructDef {
+    pub name: String,
+    pub fields: Vec<(String, TypeLiteral, usize)>, // navn, type, offset
+    pub size: usize,
+}
+
+impl StructDef {
+    pub fn field(&self, name: &str) -> Option<(TypeLiteral, usize)> {
+        self.fields
+            .iter()
+            .find(|(field, _, _)| field == name)
+            .map(|(_, typ, offset)| (*typ, *offset))
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Pointer {
+    pub ptr: usize,
+    pub len: usize,
+    pub typ: TypeLiteral,
+    pub alloc: usize, // id-en til minnet pekeren kom fra, se 'Runtime::write'
+    max_ptr: usize,
+    min_ptr: usize,
+}
+
+impl Pointer {
+    // `size` er antall celler hvert element tar i minnet.
+    pub fn new(ptr: usize, len: usize, typ: TypeLiteral, size: usize, alloc: usize) -> Self {
+        Pointer {
+            ptr,
+            len,
+            typ,
+            alloc,
+            min_ptr: ptr,
+            max_ptr: ptr + len * size,
+        }
+    }
+
+    pub fn inc(&self, offset: usize) -> Option<Self> {
+        let new_ptr = self.ptr.checked_add(offset)?;
+        if new_ptr > self.max_ptr {
+            return None;
+        }
+        Some(Pointer {
+            ptr: new_ptr,
+            ..*self
+        })
+    }
+
+    pub fn dec(&self, offset: usize) -> Option<Self> {
+        let new_ptr = self.ptr.checked_sub(offset)?;
+        if new_ptr < self.min_ptr {
+            return None;
+        }
+        Some(Pointer {
+            ptr: new_ptr,
+            ..*self
+        })
+    }
+
+    pub fn offset(&self, offset: isize) -> Option<Self> {
+        if offset >= 0 {
+            self.inc(offset as usize)
+        } else {
+            self.dec(offset.unsigned_abs())
+        }
+    }
+
+    // sjekker at `size` celler fra pekeren er innenfor grensene
+    pub fn in_bounds(&self, size: usize) -> bool {
+        self.ptr >= self.min_ptr && self.ptr + size <= self.max_ptr
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.max_ptr.saturating_sub(self.ptr)
+    }
+}
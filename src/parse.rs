@@ -1,10 +1,11 @@
-use crate::{ops, Runtime};
+use crate::diagnostic::{Diagnostic, E_LITERAL};
+use crate::{ops, report_err, Runtime};
 use snailquote::unescape;
 
 pub fn parse(
     prg: Vec<(String, ops::Pos)>,
     ctx: &mut Runtime,
-) -> Result<Vec<ops::Instruction>, (&'static str, ops::Pos)> {
+) -> Result<Vec<ops::Instruction>, Diagnostic> {
     // println!("{:?}",prg);
 
     let mut parsed_prg: Vec<ops::Instruction> = vec![];
@@ -19,15 +20,50 @@ pub fn parse(
             "skriv" => ops::Instruction::new(ops::Operator::Print, None, None, pos),
             "skrivnl" => ops::Instruction::new(ops::Operator::PrintLn, None, None, pos),
             "spør" => ops::Instruction::new(ops::Operator::Input, None, None, pos),
+            "innlinje" => ops::Instruction::new(ops::Operator::InputLine, None, None, pos),
+            "innalt" => ops::Instruction::new(ops::Operator::InputAll, None, None, pos),
+            "innbokst" => ops::Instruction::new(ops::Operator::InputChar, None, None, pos),
             "feilsøk" => ops::Instruction::new(ops::Operator::Debug, None, None, pos),
+            "argumenter" => ops::Instruction::new(ops::Operator::Args, None, None, pos),
+            "argument" => ops::Instruction::new(ops::Operator::Arg, None, None, pos),
+            "miljø" => ops::Instruction::new(ops::Operator::Env, None, None, pos),
+            "lengde" => ops::Instruction::new(ops::Operator::Len, None, None, pos),
+            "ved" => ops::Instruction::new(ops::Operator::CharAt, None, None, pos),
+            "delstr" => ops::Instruction::new(ops::Operator::Substr, None, None, pos),
+            "finn" => ops::Instruction::new(ops::Operator::Find, None, None, pos),
+            "erstatt" => ops::Instruction::new(ops::Operator::Replace, None, None, pos),
+            "del" => ops::Instruction::new(ops::Operator::Split, None, None, pos),
+            "trim" => ops::Instruction::new(ops::Operator::Trim, None, None, pos),
+            "store" => ops::Instruction::new(ops::Operator::Upper, None, None, pos),
+            "små" => ops::Instruction::new(ops::Operator::Lower, None, None, pos),
+            "begynnermed" => ops::Instruction::new(ops::Operator::StartsWith, None, None, pos),
+            "slutterpå" => ops::Instruction::new(ops::Operator::EndsWith, None, None, pos),
+            "ersiffer" => ops::Instruction::new(ops::Operator::IsDigit, None, None, pos),
+            "erbokstav" => ops::Instruction::new(ops::Operator::IsLetter, None, None, pos),
+            "ermellomrom" => ops::Instruction::new(ops::Operator::IsSpace, None, None, pos),
+            "åpne" => ops::Instruction::new(ops::Operator::Open, None, None, pos),
+            "leslinje" => ops::Instruction::new(ops::Operator::ReadLine, None, None, pos),
+            "lesalt" => ops::Instruction::new(ops::Operator::ReadAll, None, None, pos),
+            "skrivfil" => ops::Instruction::new(ops::Operator::WriteFile, None, None, pos),
+            "lukk" => ops::Instruction::new(ops::Operator::Close, None, None, pos),
             "+" => ops::Instruction::new(ops::Operator::Add, None, None, pos),
             "-" => ops::Instruction::new(ops::Operator::Sub, None, None, pos),
             "*" => ops::Instruction::new(ops::Operator::Mult, None, None, pos),
             "/" => ops::Instruction::new(ops::Operator::Div, None, None, pos),
+            "+%" => ops::Instruction::new(ops::Operator::AddWrap, None, None, pos),
+            "-%" => ops::Instruction::new(ops::Operator::SubWrap, None, None, pos),
+            "*%" => ops::Instruction::new(ops::Operator::MultWrap, None, None, pos),
+            "+|" => ops::Instruction::new(ops::Operator::AddSat, None, None, pos),
+            "-|" => ops::Instruction::new(ops::Operator::SubSat, None, None, pos),
+            "*|" => ops::Instruction::new(ops::Operator::MultSat, None, None, pos),
             "--" => ops::Instruction::new(ops::Operator::BikeShed, None, None, pos),
             "ikke" => ops::Instruction::new(ops::Operator::Not, None, None, pos),
             "og" => ops::Instruction::new(ops::Operator::And, None, None, pos),
             "eller" => ops::Instruction::new(ops::Operator::Or, None, None, pos),
+            "xeller" => ops::Instruction::new(ops::Operator::Xor, None, None, pos),
+            "<<" => ops::Instruction::new(ops::Operator::Shl, None, None, pos),
+            ">>" => ops::Instruction::new(ops::Operator::Shr, None, None, pos),
+            "bitantall" => ops::Instruction::new(ops::Operator::PopCount, None, None, pos),
             "hvis" => ops::Instruction::new(ops::Operator::If, None, None, pos),
             "ellers" => ops::Instruction::new(ops::Operator::Else, None, None, pos),
             "ellvis" => ops::Instruction::new(ops::Operator::Elif, None, None, pos),
@@ -46,6 +82,9 @@ pub fn parse(
             "minne" => ops::Instruction::new(ops::Operator::Mem, None, None, pos),
             "funk" => ops::Instruction::new(ops::Operator::Func, None, None, pos),
             "let" => ops::Instruction::new(ops::Operator::Let, None, None, pos),
+            "var" => ops::Instruction::new(ops::Operator::Var, None, None, pos),
+            "sett" => ops::Instruction::new(ops::Operator::SetVar, None, None, pos),
+            "struktur" => ops::Instruction::new(ops::Operator::Struct, None, None, pos),
             "=" => ops::Instruction::new(ops::Operator::Eq, None, None, pos),
             ">" => ops::Instruction::new(ops::Operator::Gt, None, None, pos),
             ">=" => ops::Instruction::new(ops::Operator::Ge, None, None, pos),
@@ -53,6 +92,7 @@ pub fn parse(
             "<=" => ops::Instruction::new(ops::Operator::Le, None, None, pos),
             "@" => ops::Instruction::new(ops::Operator::Read, None, None, pos),
             "->" => ops::Instruction::new(ops::Operator::Write, None, None, pos),
+            "frigjør" => ops::Instruction::new(ops::Operator::Free, None, None, pos),
 
             "Helt" => ops::Instruction::new(
                 ops::Operator::Literal,
@@ -72,6 +112,18 @@ pub fn parse(
                 None,
                 pos,
             ),
+            "Helt64" => ops::Instruction::new(
+                ops::Operator::Literal,
+                Some(ops::Value::TypeLiteral(ops::TypeLiteral::Int64)),
+                None,
+                pos,
+            ),
+            "Flyt64" => ops::Instruction::new(
+                ops::Operator::Literal,
+                Some(ops::Value::TypeLiteral(ops::TypeLiteral::Float64)),
+                None,
+                pos,
+            ),
             "Str" => ops::Instruction::new(
                 ops::Operator::Literal,
                 Some(ops::Value::TypeLiteral(ops::TypeLiteral::Str)),
@@ -90,6 +142,12 @@ pub fn parse(
                 None,
                 pos,
             ),
+            "Fil" => ops::Instruction::new(
+                ops::Operator::Literal,
+                Some(ops::Value::TypeLiteral(ops::TypeLiteral::File)),
+                None,
+                pos,
+            ),
             "sann" => ops::Instruction::new(
                 ops::Operator::Literal,
                 Some(ops::Value::Bool(true)),
@@ -114,16 +172,41 @@ pub fn parse(
                 None,
                 pos,
             ),
+            x if x.parse::<i64>().is_ok() => {
+                report_err!(E_LITERAL, pos, "{} er for stort for 'Helt', skriv {}L for en 'Helt64'", x, x);
+            }
             x if x.parse::<f32>().is_ok() => ops::Instruction::new(
                 ops::Operator::Literal,
                 Some(ops::Value::Float(x.parse::<f32>().unwrap())),
                 None,
                 pos,
             ),
-            x if x.chars().nth(0) == Some('"') => {
+            // tall med 'L' på slutten er 'Helt64', og med 'D' er 'Flyt64'
+            x if x.strip_suffix('L').is_some_and(|x| x.parse::<i64>().is_ok()) => ops::Instruction::new(
+                ops::Operator::Literal,
+                Some(ops::Value::Int64(x[..x.len() - 1].parse::<i64>().unwrap())),
+                None,
+                pos,
+            ),
+            x if x.strip_suffix('D').is_some_and(|x| x.parse::<f64>().is_ok()) => ops::Instruction::new(
+                ops::Operator::Literal,
+                Some(ops::Value::Float64(x[..x.len() - 1].parse::<f64>().unwrap())),
+                None,
+                pos,
+            ),
+            x if x.starts_with('.') && x.len() > 1 => ops::Instruction::new(
+                ops::Operator::Field,
+                None,
+                Some(x[1..].to_string()),
+                pos,
+            ),
+            x if x.starts_with('"') => {
+                if x.len() < 2 || !x.ends_with('"') || unescape(x).is_err() {
+                    report_err!(E_LITERAL, pos, "Ugyldig streng {}", x);
+                }
                 let unescaped_x = parse_char(x);
 
-                if unescaped_x.len() <= 1 {
+                if unescaped_x.len() == 1 {
                     ops::Instruction::new(
                         ops::Operator::Literal,
                         Some(unescaped_x[0].clone()),
@@ -131,7 +214,7 @@ pub fn parse(
                         pos,
                     )
                 } else {
-                    let res = ctx.write(&unescaped_x);
+                    let res = ctx.write_literal(&unescaped_x);
                     ops::Instruction::new(
                         ops::Operator::Literal,
                         Some(ops::Value::Str(res)),
@@ -140,44 +223,13 @@ pub fn parse(
                     )
                 }
             }
-            // x if x.chars().nth(0) == Some('b') => {
-            //     if x.len() != 9 {
-            //         return Err(("Kunne ikke oversette 'Byte'", pos));
-            //     }
-
-            //     let mut byte = x.chars();
-            //     byte.next();
-            //     let res = u8::from_str_radix(byte.as_str(), 2);
-            //     if let Ok(u) = res {
-            //         ops::Instruction::new(
-            //             ops::Operator::Literal,
-            //             Some(ops::Value::Byte(u)),
-            //             None,
-            //             pos,
-            //         )
-            //     } else {
-            //         return Err(("Kunne ikke oversette 'Byte'", pos));
-            //     }
-            // }
-            // x if x.chars().nth(0) == Some('x') => {
-            //     if x.len() != 3 {
-            //         return Err(("Kunne ikke oversette 'Byte'", pos));
-            //     }
-
-            //     let mut byte = x.chars();
-            //     byte.next();
-            //     let res = u8::from_str_radix(byte.as_str(), 16);
-            //     if let Ok(u) = res {
-            //         ops::Instruction::new(
-            //             ops::Operator::Literal,
-            //             Some(ops::Value::Byte(u)),
-            //             None,
-            //             pos,
-            //         )
-            //     } else {
-            //         return Err(("Kunne ikke oversette 'Byte'", pos));
-            //     }
-            // }
+            // 'b' og 8 binære sifre, eller 'x' og 2 heksadesimale sifre
+            x if byte_literal(x).is_some() => ops::Instruction::new(
+                ops::Operator::Literal,
+                Some(ops::Value::Byte(byte_literal(x).unwrap())),
+                None,
+                pos,
+            ),
             "" => {
                 i += 1;
                 continue;
@@ -195,6 +247,14 @@ pub fn parse(
     Ok(parsed_prg)
 }
 
+fn byte_literal(x: &str) -> Option<u8> {
+    match (x.strip_prefix('b'), x.strip_prefix('x')) {
+        (Some(bits), _) if bits.len() == 8 && bits.chars().all(|c| c == '0' || c == '1') => u8::from_str_radix(bits, 2).ok(),
+        (_, Some(hex)) if hex.len() == 2 && hex.chars().all(|c| c.is_ascii_hexdigit()) => u8::from_str_radix(hex, 16).ok(),
+        _ => None,
+    }
+}
+
 pub fn parse_char(x: &str) -> Vec<ops::Value> {
     let quoted = unescape(x).unwrap();
     let unescaped_x = quoted.chars();
@@ -0,0 +1,175 @@
+use crate::execute::Hook;
+use crate::trace::token_text;
+use crate::{ops, Runtime};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::time::{Duration, Instant};
+
+const TOP: usize = 10;
+const MAIN: &str = "hoved"; // navnet på toppnivået i 'folded' filen
+
+#[derive(Default)]
+struct FuncStats {
+    calls: u64,
+    time: Duration, // tid fra 'Runtime::call' til 'Runtime::retur', med funksjonene den kaller
+    instructions: u64,
+    mem: usize, // minne celler lagt til mens funksjonen kjørte
+}
+
+// Teller instruksjoner og funksjons kall, og skriver en rapport til stderr når
+// programmet er ferdig. Med en fil skrives også en 'folded stack' fil som kan
+// brukes til å lage flammegrafer, der hver instruksjon teller som én prøve.
+pub struct Profiler {
+    counts: Vec<u64>, // antall ganger hver instruksjon er kjørt
+    funcs: HashMap<String, FuncStats>,
+    calls: Vec<(String, Instant, usize)>, // navn, når kallet startet, lengden på `stack_key` før kallet
+    stack_key: String,
+    folded: HashMap<String, u64>,
+    folded_path: Option<String>,
+    start: Instant,
+    depth_before: usize,
+    mem_before: usize,
+    mem_start: Option<usize>,
+    mem_peak: usize,
+}
+
+impl Profiler {
+    pub fn new(folded_path: Option<String>) -> Self {
+        Profiler {
+            counts: vec![],
+            funcs: HashMap::new(),
+            calls: vec![],
+            stack_key: String::from(MAIN),
+            folded: HashMap::new(),
+            folded_path,
+            start: Instant::now(),
+            depth_before: 0,
+            mem_before: 0,
+            mem_start: None,
+            mem_peak: 0,
+        }
+    }
+
+    fn current(&mut self) -> Option<&mut FuncStats> {
+        let (name, _, _) = self.calls.last()?;
+        self.funcs.get_mut(name)
+    }
+
+    fn leave(&mut self) {
+        if let Some((name, start, key_len)) = self.calls.pop() {
+            self.funcs.entry(name).or_default().time += start.elapsed();
+            self.stack_key.truncate(key_len);
+        }
+    }
+
+    fn report(&self, ctx: &Runtime, prg: &[ops::Instruction]) {
+        let total: u64 = self.counts.iter().sum();
+        eprintln!();
+        eprintln!("== profil ==");
+        eprintln!("{} instruksjoner på {:.3} ms", total, ms(self.start.elapsed()));
+        eprintln!(
+            "minne: {} celler ved start, {} på det meste, {} ved slutt",
+            self.mem_start.unwrap_or(0),
+            self.mem_peak,
+            ctx.mem.len()
+        );
+
+        let mut funcs: Vec<_> = self.funcs.iter().collect();
+        funcs.sort_by(|a, b| b.1.time.cmp(&a.1.time).then(a.0.cmp(b.0)));
+        if !funcs.is_empty() {
+            eprintln!();
+            eprintln!("{:>10} {:>12} {:>12} {:>14} {:>8}  funksjon", "kall", "tid ms", "ms per kall", "instruksjoner", "minne");
+            for (name, x) in funcs {
+                let per_call = ms(x.time) / x.calls.max(1) as f64;
+                eprintln!("{:>10} {:>12.3} {:>12.4} {:>14} {:>8}  {}", x.calls, ms(x.time), per_call, x.instructions, x.mem, name);
+            }
+        }
+
+        let mut lines: HashMap<(&str, usize), u64> = HashMap::new();
+        for (i, count) in self.counts.iter().enumerate().filter(|(_, x)| **x > 0) {
+            *lines.entry((&prg[i].pos.2, prg[i].pos.0)).or_default() += count;
+        }
+        let mut lines: Vec<_> = lines.into_iter().collect();
+        lines.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        eprintln!();
+        eprintln!("{:>10}  linje", "antall");
+        for ((file, line), count) in lines.iter().take(TOP) {
+            eprintln!("{:>10}  {}:{}", count, file, line);
+        }
+
+        let mut insts: Vec<_> = self.counts.iter().enumerate().filter(|(_, x)| **x > 0).collect();
+        insts.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(&b.0)));
+        eprintln!();
+        eprintln!("{:>10}  instruksjon", "antall");
+        for (i, count) in insts.iter().take(TOP) {
+            let pos = &prg[*i].pos;
+            eprintln!("{:>10}  {}:{}:{} {}", count, pos.2, pos.0, pos.1, token_text(ctx, &prg[*i]));
+        }
+    }
+
+    fn write_folded(&self, path: &str) -> std::io::Result<()> {
+        let mut stacks: Vec<_> = self.folded.iter().collect();
+        stacks.sort();
+        let mut out = BufWriter::new(File::create(path)?);
+        for (stack, count) in stacks {
+            writeln!(out, "{} {}", stack, count)?;
+        }
+        out.flush()
+    }
+}
+
+impl Hook for Profiler {
+    fn before(&mut self, ctx: &Runtime, _prg: &[ops::Instruction], i: usize) {
+        if self.counts.len() <= i {
+            self.counts.resize(i + 1, 0);
+        }
+        self.counts[i] += 1;
+        self.mem_start.get_or_insert(ctx.mem.len());
+        self.depth_before = ctx.return_stack.len();
+        self.mem_before = ctx.mem.len();
+        if let Some(func) = self.current() {
+            func.instructions += 1
+        }
+        if self.folded_path.is_some() {
+            *self.folded.entry(self.stack_key.clone()).or_default() += 1;
+        }
+    }
+
+    fn after(&mut self, ctx: &Runtime, prg: &[ops::Instruction], i: usize) {
+        let grown = ctx.mem.len().saturating_sub(self.mem_before);
+        if let Some(func) = self.current() {
+            func.mem += grown
+        }
+        self.mem_peak = self.mem_peak.max(ctx.mem.len());
+
+        let depth = ctx.return_stack.len();
+        if depth > self.depth_before {
+            let name = prg[i].name.clone().unwrap_or_default();
+            self.funcs.entry(name.clone()).or_default().calls += 1;
+            let key_len = self.stack_key.len();
+            self.stack_key.push(';');
+            self.stack_key.push_str(&name);
+            self.calls.push((name, Instant::now(), key_len));
+        } else if depth < self.depth_before {
+            self.leave()
+        }
+    }
+
+    fn finish(&mut self, ctx: &Runtime, prg: &[ops::Instruction]) {
+        // funksjoner som ikke har returnert når programmet stopper
+        while !self.calls.is_empty() {
+            self.leave()
+        }
+        self.report(ctx, prg);
+        if let Some(path) = &self.folded_path {
+            if let Err(e) = self.write_folded(path) {
+                eprintln!("Kunne ikke skrive til '{}': {}", path, e);
+            }
+        }
+    }
+}
+
+fn ms(x: Duration) -> f64 {
+    x.as_secs_f64() * 1000.
+}
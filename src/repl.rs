@@ -0,0 +1,130 @@
+use crate::diagnostic::Diagnostic;
+use crate::check::{self, Type};
+use crate::{cross_ref, execute, lex, ops, parse, Runtime};
+use std::io::{stdin, stdout, Write};
+
+const PATH: &str = "<repl>";
+
+const HELP: &str = ":stabel         skriv ut stabelen
+:minne          skriv ut minnet
+:last <fil>     kjør en fil inn i denne økten
+:nullstill      start på nytt med tom stabel og minne
+:hjelp          vis denne teksten
+:avslutt        avslutt";
+
+// Alt som er skrevet inn blir lagt etter hverandre i `prg`, så funksjoner og
+// blokker fra tidligere linjer fortsatt peker på riktige instruksjoner.
+pub fn run(args: Vec<String>) {
+    let mut ctx = Runtime {
+        args,
+        ..Runtime::new()
+    };
+    let mut prg: Vec<ops::Instruction> = vec![];
+    println!("Æ++ {} (skriv ':hjelp' for hjelp)", env!("CARGO_PKG_VERSION"));
+
+    while let Some(input) = read_input() {
+        let cmd = input.trim();
+        if cmd.is_empty() {
+            continue;
+        }
+
+        let res = match cmd.split_once(' ').unwrap_or((cmd, "")) {
+            (":stabel", _) => {
+                println!("{}", ctx.stack_line());
+                continue;
+            }
+            (":minne", _) => {
+                println!("{}", ctx.mem_line());
+                continue;
+            }
+            (":nullstill", _) => {
+                ctx = Runtime {
+                    args: std::mem::take(&mut ctx.args),
+                    ..Runtime::new()
+                };
+                prg.clear();
+                continue;
+            }
+            (":hjelp", _) => {
+                println!("{}", HELP);
+                continue;
+            }
+            (":avslutt", _) => break,
+            (":last", path) => lex::lex(&path.trim().to_string()).map_err(|e| vec![e]),
+            (x, _) if x.starts_with(':') => {
+                println!("Ukjent kommando '{}', skriv ':hjelp' for hjelp", x);
+                continue;
+            }
+            _ => lex::lex_str(&input, PATH).map_err(|e| vec![e]),
+        };
+
+        let start = prg.len();
+        let res = res.and_then(|tokens| compile(tokens, &mut ctx, &mut prg));
+        let res = res.and_then(|_| execute::execute_from(&mut ctx, &prg, start).map_err(|e| vec![e]));
+        if let Err(errors) = res {
+            for e in errors {
+                eprintln!("{}", e)
+            }
+            ctx.unwind();
+        }
+        print!("{}", ctx);
+    }
+    println!();
+}
+
+// Leser en linje, og flere så lenge det finnes blokker som ikke er stengt.
+fn read_input() -> Option<String> {
+    let mut input = String::new();
+    let mut depth = 0;
+    loop {
+        print!("{}", if input.is_empty() { "æ++> " } else { "...> " });
+        let _ = stdout().flush();
+
+        let mut line = String::new();
+        if stdin().read_line(&mut line).ok()? == 0 {
+            return if input.is_empty() { None } else { Some(input) };
+        }
+        if input.is_empty() && line.trim_start().starts_with(':') {
+            return Some(line);
+        }
+
+        for (token, _) in lex::lex_str(&line, PATH).unwrap_or_default() {
+            if lex::OPENERS.contains(&token.as_str()) {
+                depth += 1
+            } else if token == "slutt" {
+                depth -= 1
+            }
+        }
+        input.push_str(&line);
+        if depth <= 0 {
+            return Some(input);
+        }
+    }
+}
+
+// Gjør de nye ordene om til instruksjoner og legger dem etter programmet som allerede er kjørt.
+// De nye instruksjonene blir typesjekket med stabelen slik den er nå, og fjernet igjen hvis
+// sjekken feiler.
+fn compile(
+    tokens: Vec<(String, ops::Pos)>,
+    ctx: &mut Runtime,
+    prg: &mut Vec<ops::Instruction>,
+) -> Result<(), Vec<Diagnostic>> {
+    let mut parsed = parse::parse(tokens, ctx).map_err(|e| vec![e])?;
+    let cross_refed = cross_ref::cross_reference(&mut parsed, ctx);
+    for warning in ctx.warnings.drain(..) {
+        eprintln!("{}", warning)
+    }
+
+    let offset = prg.len();
+    for mut inst in cross_refed? {
+        inst.arg = inst.arg.map(|x| x + offset);
+        prg.push(inst);
+    }
+    let stack = ctx.stack.iter().map(Type::from_value).collect();
+    if let Err(e) = check::check_from(prg, ctx, offset, stack) {
+        prg.truncate(offset);
+        return Err(e);
+    }
+    Ok(())
+}
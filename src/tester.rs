@@ -0,0 +1,104 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+// Kjører hver `navn_test.ae` i `dir` som et eget program. Filer ved siden av
+// testen med samme navn bestemmer hva som blir sjekket:
+//   navn_test.ut    det programmet skal skrive til stdout
+//   navn_test.inn   det programmet får på stdin
+//   navn_test.arg   argumenter til programmet, skilt med mellomrom
+//   navn_test.kode  avslutnings koden programmet skal gi, ellers 0
+//   navn_test.feil  det programmet skal skrive til stderr, med stier relative
+//                   til mappen testen ligger i
+pub fn run(dir: &str) -> i32 {
+    let Ok(entries) = fs::read_dir(dir) else {
+        eprintln!("Fant ikke mappen '{}'", dir);
+        return 1;
+    };
+    let mut tests: Vec<PathBuf> = entries
+        .filter_map(|x| x.ok().map(|x| x.path()))
+        .filter(|x| x.to_string_lossy().ends_with("_test.ae"))
+        .collect();
+    tests.sort();
+
+    let mut failed = 0;
+    for test in &tests {
+        match run_test(test) {
+            Ok(()) => println!("ok   {}", test.display()),
+            Err(e) => {
+                println!("FEIL {}", test.display());
+                for line in e.lines() {
+                    println!("     {}", line)
+                }
+                failed += 1
+            }
+        }
+    }
+    println!();
+    println!("{} bestått, {} feilet", tests.len() - failed, failed);
+    if failed > 0 {
+        1
+    } else {
+        0
+    }
+}
+
+fn run_test(test: &Path) -> Result<(), String> {
+    let read = |ext: &str| fs::read_to_string(test.with_extension(ext)).ok();
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let mut cmd = Command::new(exe);
+    cmd.arg("kjør").arg(test);
+    if let Some(args) = read("arg") {
+        cmd.arg("--").args(args.split_whitespace());
+    }
+    let input = read("inn");
+    let mut child = cmd
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        let _ = stdin.write_all(input.as_bytes());
+    }
+    let out = child.wait_with_output().map_err(|e| e.to_string())?;
+
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    let expected_code = match read("kode") {
+        Some(x) => x.trim().parse().map_err(|_| format!("Ugyldig avslutnings kode '{}'", x.trim()))?,
+        None => 0,
+    };
+    let code = out.status.code().unwrap_or(-1);
+    if code != expected_code {
+        return Err(format!(
+            "avsluttet med {}, forventet {}\n{}",
+            code,
+            expected_code,
+            stderr.trim_end()
+        ));
+    }
+    if let Some(expected) = read("ut") {
+        if stdout != expected {
+            return Err(format!(
+                "forventet:\n{}\nfikk:\n{}",
+                expected.trim_end(),
+                stdout.trim_end()
+            ));
+        }
+    }
+    if let Some(expected) = read("feil") {
+        // stiene i feilmeldingene starter med mappen testen ble kjørt fra
+        let dir = test.parent().map(|x| format!("{}/", x.display())).unwrap_or_default();
+        let stderr = if dir == "/" { stderr.to_string() } else { stderr.replace(&dir, "") };
+        if stderr != expected {
+            return Err(format!(
+                "forventet på stderr:\n{}\nfikk:\n{}",
+                expected.trim_end(),
+                stderr.trim_end()
+            ));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,118 @@
+use crate::check::op_name;
+use crate::execute::Hook;
+use crate::{ops, Runtime};
+use std::io::Write;
+use std::path::Path;
+
+// Begrenser hvilke instruksjoner som blir skrevet ut av '--spor'.
+#[derive(Default)]
+pub struct Filter {
+    pub file: Option<String>,
+    pub lines: Option<(usize, usize)>,
+    pub func: Option<String>, // bare instruksjoner som kjøres direkte i denne funksjonen
+}
+
+impl Filter {
+    fn matches(&self, ctx: &Runtime, prg: &[ops::Instruction], i: usize) -> bool {
+        let pos = &prg[i].pos;
+        if let Some(file) = &self.file {
+            if pos.2 != *file && !Path::new(&pos.2).ends_with(file) {
+                return false;
+            }
+        }
+        if let Some((from, to)) = self.lines {
+            if pos.0 < from || pos.0 > to {
+                return false;
+            }
+        }
+        if let Some(func) = &self.func {
+            if ctx.call_trace(prg).first().is_none_or(|(name, _)| name != func) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// '12' eller '10-20'
+pub fn parse_lines(x: &str) -> Option<(usize, usize)> {
+    match x.split_once('-') {
+        Some((from, to)) => Some((from.parse().ok()?, to.parse().ok()?)),
+        None => {
+            let line = x.parse().ok()?;
+            Some((line, line))
+        }
+    }
+}
+
+// Skriver én linje for hver instruksjon som kjøres:
+// fil:linje:kolonne<TAB>ord<TAB>[stabel før]<TAB>[stabel etter]
+// Instruksjonen som stopper programmet med en feil får '!' som stabel etter.
+pub struct Tracer {
+    out: Box<dyn Write>,
+    filter: Filter,
+    pending: Option<(usize, String)>,
+}
+
+impl Tracer {
+    pub fn new(out: Box<dyn Write>, filter: Filter) -> Self {
+        Tracer {
+            out,
+            filter,
+            pending: None,
+        }
+    }
+
+    fn write_line(&mut self, ctx: &Runtime, token: &ops::Instruction, before: &str, after: &str) {
+        let pos = &token.pos;
+        let word = token_text(ctx, token);
+        let _ = writeln!(self.out, "{}:{}:{}\t{}\t[{}]\t{}", pos.2, pos.0, pos.1, word, before, after);
+    }
+}
+
+impl Hook for Tracer {
+    fn before(&mut self, ctx: &Runtime, prg: &[ops::Instruction], i: usize) {
+        if self.filter.matches(ctx, prg, i) {
+            self.pending = Some((i, show_stack(ctx)));
+        }
+    }
+
+    fn after(&mut self, ctx: &Runtime, prg: &[ops::Instruction], _i: usize) {
+        if let Some((i, before)) = self.pending.take() {
+            let after = format!("[{}]", show_stack(ctx));
+            self.write_line(ctx, &prg[i], &before, &after)
+        }
+    }
+
+    // en instruksjon som fortsatt venter her var den som stoppet programmet
+    fn finish(&mut self, ctx: &Runtime, prg: &[ops::Instruction]) {
+        if let Some((i, before)) = self.pending.take() {
+            self.write_line(ctx, &prg[i], &before, "!")
+        }
+        let _ = self.out.flush();
+    }
+}
+
+pub fn token_text(ctx: &Runtime, token: &ops::Instruction) -> String {
+    match (token.op, &token.name, &token.val) {
+        (ops::Operator::Field, Some(name), _) => format!(".{}", name),
+        (_, Some(name), _) => name.clone(),
+        (ops::Operator::Literal, _, Some(val)) => show_value(ctx, val),
+        (op, _, _) => op_name(op).to_string(),
+    }
+}
+
+pub fn show_value(ctx: &Runtime, val: &ops::Value) -> String {
+    match val {
+        ops::Value::Str(_) => format!("{:?}", val.to_string(ctx)),
+        ops::Value::Char(x) => format!("{:?}", x),
+        ops::Value::Ptr(x) => format!("Peker({};{})@{}", x.typ, x.len, x.ptr),
+        ops::Value::FuncPtr(_) => String::from("funk"),
+        _ => val.to_string(ctx),
+    }
+}
+
+fn show_stack(ctx: &Runtime) -> String {
+    let vals: Vec<String> = ctx.stack.iter().map(|x| show_value(ctx, x)).collect();
+    vals.join(" ")
+}